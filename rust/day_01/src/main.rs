@@ -1,74 +1,524 @@
-use std::fs;
-
-fn part_one(input: &str) -> u32 {
-    input
-        .lines()
-        .map(|line| {
-            let nums: Vec<_> = line
-                .chars().filter_map(|x| x.to_digit(10)).collect();
-            nums[0] * 10 + nums.last().unwrap()
-        })
-        .sum()
-}
-
-fn extract_first_and_last(input: &str) -> (u32, u32) {
-    // Do two pointers where the tail is iterated over to see if a string exists. 
-    let mut nums = Vec::new();
-    let chars: Vec<_> = input.chars().collect();
-    let mut tail = 0;
-    for head in 0..input.len() {
-        if let Some(val) = chars[head].to_digit(10){
-            // if it's a digit push and reset the tail
-            nums.push(val);
-            tail = head
-        } else {
-            for trailing in tail..head {
-                if let Some(val) = match &input[trailing..=head] {
-                    "one" => Some(1),
-                    "two" => Some(2),
-                    "three" => Some(3),
-                    "four" => Some(4),
-                    "five" => Some(5),
-                    "six" => Some(6),
-                    "seven" => Some(7),
-                    "eight" => Some(8),
-                    "nine" => Some(9),
-                    _ => None,
-                } {
-                    nums.push(val);
-                } else if head - tail >= 4 {
-                    // largest number of characters is 5
-                    // since our search includes the head its' > 4
-                    tail +=1
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::sync::Mutex;
+use std::{fs, str::FromStr, thread};
+
+// how many lines each worker pulls off the input at a time when calibrating in parallel
+const CHUNK_LINES: usize = 4096;
+
+const SPELLED_DIGITS: [(&str, u32); 9] = [
+    ("one", 1),
+    ("two", 2),
+    ("three", 3),
+    ("four", 4),
+    ("five", 5),
+    ("six", 6),
+    ("seven", 7),
+    ("eight", 8),
+    ("nine", 9),
+];
+
+/// A token found in a line, as byte offsets into it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Match {
+    start: usize,
+    end: usize,
+    value: u32,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    next: HashMap<char, usize>,
+    fail: usize,
+    // (length in bytes, value) of every word that ends here, including through fail links
+    words: Vec<(usize, u32)>,
+}
+
+/// The words we recognize as digits, built into an Aho-Corasick automaton so a line can be
+/// searched for all of them, overlaps included, in one pass
+#[derive(Debug)]
+struct Vocabulary {
+    states: Vec<State>,
+}
+
+impl Vocabulary {
+    fn new<'a>(words: impl IntoIterator<Item = (&'a str, u32)>) -> Self {
+        // start with a trie of every word
+        let mut states = vec![State::default()];
+        for (word, value) in words {
+            let mut current = 0;
+            for letter in word.chars() {
+                current = match states[current].next.get(&letter) {
+                    Some(&next) => next,
+                    None => {
+                        states.push(State::default());
+                        let next = states.len() - 1;
+                        states[current].next.insert(letter, next);
+                        next
+                    }
+                };
+            }
+            states[current].words.push((word.len(), value));
+        }
+
+        // then go breadth first, pointing every state at the longest proper suffix of it that's
+        // also in the trie, which is where we pick up from when the next letter doesn't match
+        let mut to_visit: VecDeque<usize> = states[0].next.values().copied().collect();
+        while let Some(current) = to_visit.pop_front() {
+            let transitions: Vec<(char, usize)> =
+                states[current].next.iter().map(|(&c, &s)| (c, s)).collect();
+            for (letter, child) in transitions {
+                let mut fail = states[current].fail;
+                while fail != 0 && !states[fail].next.contains_key(&letter) {
+                    fail = states[fail].fail;
                 }
+                let fail = match states[fail].next.get(&letter) {
+                    Some(&next) if next != child => next,
+                    _ => 0,
+                };
+                states[child].fail = fail;
+                let inherited = states[fail].words.clone();
+                states[child].words.extend(inherited);
+                to_visit.push_back(child);
             }
         }
+        Self { states }
+    }
+
+    fn digits() -> Self {
+        Self::new(
+            ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"]
+                .into_iter()
+                .zip(0..),
+        )
+    }
+
+    fn spelled_digits() -> Self {
+        Self::new(
+            ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"]
+                .into_iter()
+                .zip(0..)
+                .chain(SPELLED_DIGITS),
+        )
+    }
+
+    fn find_all(&self, input: &str) -> Vec<Match> {
+        let mut matches = Vec::new();
+        let mut current = 0;
+        for (i, letter) in input.char_indices() {
+            while current != 0 && !self.states[current].next.contains_key(&letter) {
+                current = self.states[current].fail;
+            }
+            current = *self.states[current].next.get(&letter).unwrap_or(&0);
+            let end = i + letter.len_utf8();
+            matches.extend(
+                self.states[current]
+                    .words
+                    .iter()
+                    .map(|&(len, value)| Match {
+                        start: end - len,
+                        end,
+                        value,
+                    }),
+            );
+        }
+        matches
     }
-    (nums[0], *nums.last().unwrap())
 }
 
-fn part_two(input: &str) -> u32{
-    input
-        .lines()
-        .map(|line| extract_first_and_last(line))
-        .map(|(first, last)| first * 10 + last)
-        .sum()
+impl FromStr for Vocabulary {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // one `word value` pair per line, e.g. `zwei 2`. This is the whole vocabulary, so plain
+        // digits need listing too if they should count
+        let entries = s
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let (word, value) = line
+                    .trim()
+                    .rsplit_once(char::is_whitespace)
+                    .ok_or(format!("Expected a word and a value: {line}"))?;
+                let value = value
+                    .parse::<u32>()
+                    .map_err(|_| format!("Couldn't parse value: {line}"))?;
+                Ok((word.trim(), value))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Self::new(entries))
+    }
+}
+
+fn first_and_last(input: &str, vocabulary: &Vocabulary) -> Option<(Match, Match)> {
+    // the tokens that start first and last, so words that share a letter like `eightwo` count
+    // for both ends. If two tokens start together the longer one wins
+    let matches = vocabulary.find_all(input);
+    let first = matches
+        .iter()
+        .min_by_key(|token| (token.start, usize::MAX - token.end))?;
+    let last = matches
+        .iter()
+        .max_by_key(|token| (token.start, token.end))?;
+    Some((*first, *last))
+}
+
+fn extract(input: &str, vocabulary: &Vocabulary) -> Option<(u32, u32)> {
+    first_and_last(input, vocabulary).map(|(first, last)| (first.value, last.value))
+}
+
+fn read_line(input: &mut impl BufRead, buffer: &mut Vec<u8>) -> io::Result<bool> {
+    // reads the next line into `buffer` without its line ending, returning false once the
+    // input runs out. Works on raw bytes so a stray bit of invalid UTF-8 doesn't stop us
+    buffer.clear();
+    if input.read_until(b'\n', buffer)? == 0 {
+        return Ok(false);
+    }
+    while buffer
+        .last()
+        .is_some_and(|&byte| byte == b'\n' || byte == b'\r')
+    {
+        buffer.pop();
+    }
+    Ok(true)
+}
+
+fn combine(first: u32, last: u32) -> u64 {
+    // custom vocabularies can map a word to any u32, so widen before shifting the first one over
+    first as u64 * 10 + last as u64
+}
+
+fn calibration_value(line: &[u8], vocabulary: &Vocabulary) -> u64 {
+    extract(&String::from_utf8_lossy(line), vocabulary)
+        .map_or(0, |(first, last)| combine(first, last))
+}
+
+fn calibrate(mut input: impl BufRead, vocabulary: &Vocabulary) -> io::Result<u64> {
+    // one line in memory at a time, however big the input is
+    let mut buffer = Vec::new();
+    let mut total = 0;
+    while read_line(&mut input, &mut buffer)? {
+        total += calibration_value(&buffer, vocabulary);
+    }
+    Ok(total)
+}
+
+fn calibrate_parallel(
+    input: impl BufRead + Send,
+    vocabulary: &Vocabulary,
+    threads: usize,
+) -> io::Result<u64> {
+    // each worker takes turns pulling a chunk of lines off the shared input, then scans them
+    // on its own, so at most `threads` chunks are held in memory at once
+    let input = Mutex::new(input);
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.max(1))
+            .map(|_| {
+                scope.spawn(|| -> io::Result<u64> {
+                    let mut chunk = vec![Vec::new(); CHUNK_LINES];
+                    let mut total = 0;
+                    loop {
+                        let mut lines = 0;
+                        {
+                            let mut input = input.lock().unwrap();
+                            while lines < CHUNK_LINES && read_line(&mut *input, &mut chunk[lines])?
+                            {
+                                lines += 1;
+                            }
+                        }
+                        if lines == 0 {
+                            return Ok(total);
+                        }
+                        total += chunk[..lines]
+                            .iter()
+                            .map(|line| calibration_value(line, vocabulary))
+                            .sum::<u64>();
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("Expected worker to finish"))
+            .sum()
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReportFormat {
+    Csv,
+    Table,
+}
+
+fn token_cells(line: &str, token: Option<Match>) -> [String; 2] {
+    // the token as it appears in the line, and which character it starts on
+    match token {
+        Some(token) => [
+            line[token.start..token.end].to_string(),
+            line[..token.start].chars().count().to_string(),
+        ],
+        None => ["-".to_string(), "-".to_string()],
+    }
+}
+
+fn diagnose(input: &str, format: ReportFormat) -> String {
+    // lines up what each part's rules found on every line, so we can see where they disagree
+    let (digits, spelled) = (Vocabulary::digits(), Vocabulary::spelled_digits());
+    let header = [
+        "line", "p1 first", "pos", "p1 last", "pos", "p1", "p2 first", "pos", "p2 last", "pos",
+        "p2", "flags", "text",
+    ];
+    let mut rows: Vec<Vec<String>> = vec![header.iter().map(|cell| cell.to_string()).collect()];
+    for (i, line) in input.lines().enumerate() {
+        let mut row = vec![(i + 1).to_string()];
+        let mut values = Vec::new();
+        for vocabulary in [&digits, &spelled] {
+            let found = first_and_last(line, vocabulary);
+            row.extend(token_cells(line, found.map(|(first, _)| first)));
+            row.extend(token_cells(line, found.map(|(_, last)| last)));
+            let value = found.map(|(first, last)| combine(first.value, last.value));
+            row.push(value.map_or("-".to_string(), |value| value.to_string()));
+            values.push(value);
+        }
+        let flags = match (values[0], values[1]) {
+            (None, None) => "no digits",
+            (None, Some(_)) => "words only",
+            (Some(one), Some(two)) if one != two => "differs",
+            _ => "",
+        };
+        row.push(flags.to_string());
+        row.push(line.to_string());
+        rows.push(row);
+    }
+
+    match format {
+        ReportFormat::Csv => rows
+            .iter()
+            .map(|row| {
+                let cells: Vec<String> = row
+                    .iter()
+                    .map(|cell| {
+                        if cell.contains([',', '"']) {
+                            format!("\"{}\"", cell.replace('"', "\"\""))
+                        } else {
+                            cell.clone()
+                        }
+                    })
+                    .collect();
+                cells.join(",") + "\n"
+            })
+            .collect(),
+        ReportFormat::Table => {
+            let widths: Vec<usize> = (0..header.len())
+                .map(|col| {
+                    rows.iter()
+                        .map(|row| row[col].chars().count())
+                        .max()
+                        .unwrap_or(0)
+                })
+                .collect();
+            rows.iter()
+                .map(|row| {
+                    let cells: Vec<String> = row
+                        .iter()
+                        .zip(widths.iter())
+                        .map(|(cell, &width)| format!("{cell:<width$}"))
+                        .collect();
+                    cells.join(" | ").trim_end().to_string() + "\n"
+                })
+                .collect()
+        }
+    }
+}
+
+fn part_one(input: impl BufRead) -> io::Result<u64> {
+    calibrate(input, &Vocabulary::digits())
+}
+
+fn part_two(input: impl BufRead) -> io::Result<u64> {
+    calibrate(input, &Vocabulary::spelled_digits())
 }
 
 fn main() {
-    let input = fs::read_to_string("rust/day_01/input.txt").expect("Expected to load puzzle input");
+    let path = "rust/day_01/input.txt";
+    let open = || BufReader::new(File::open(path).expect("Expected to load puzzle input"));
+
+    if let Some(threads) = std::env::args().skip_while(|arg| arg != "--threads").nth(1) {
+        let threads = threads.parse().expect("Expected a number of threads");
+        let part_one = calibrate_parallel(open(), &Vocabulary::digits(), threads);
+        let part_two = calibrate_parallel(open(), &Vocabulary::spelled_digits(), threads);
+        println!(
+            "Part One Solution: {}",
+            part_one.expect("Expected to read input")
+        );
+        println!(
+            "Part Two Solution: {}",
+            part_two.expect("Expected to read input")
+        );
+    } else {
+        println!(
+            "Part One Solution: {}",
+            part_one(open()).expect("Expected to read input")
+        );
+        println!(
+            "Part Two Solution: {}",
+            part_two(open()).expect("Expected to read input")
+        );
+    }
 
-    println!("Part One Solution: {}", part_one(&input));
-    println!("Part Two Solution: {}", part_two(&input));
+    if let Some(vocabulary_path) = std::env::args()
+        .skip_while(|arg| arg != "--vocabulary")
+        .nth(1)
+    {
+        let vocabulary: Vocabulary = fs::read_to_string(vocabulary_path)
+            .expect("Expected to load vocabulary")
+            .parse()
+            .expect("Expected to parse vocabulary");
+        println!(
+            "Custom Vocabulary Solution: {}",
+            calibrate(open(), &vocabulary).expect("Expected to read input")
+        );
+    }
+    if let Some(format) = std::env::args()
+        .skip_while(|arg| arg != "--diagnose")
+        .nth(1)
+    {
+        let format = match format.as_str() {
+            "csv" => ReportFormat::Csv,
+            "table" => ReportFormat::Table,
+            _ => panic!("Expected a report format of csv or table"),
+        };
+        let input = fs::read_to_string(path).expect("Expected to load puzzle input");
+        print!("{}", diagnose(&input, format));
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    fn extract_first_and_last(input: &str) -> Option<(u32, u32)> {
+        extract(input, &Vocabulary::spelled_digits())
+    }
+
     #[test]
     fn test_extraction() {
         let act = extract_first_and_last("abcone2threexyz");
-        assert_eq!(act, (1, 3));
+        assert_eq!(act, Some((1, 3)));
+    }
+
+    #[test]
+    fn test_overlapping_words() {
+        assert_eq!(extract_first_and_last("eightwo"), Some((8, 2)));
+        assert_eq!(extract_first_and_last("zoneight234"), Some((1, 4)));
+        assert_eq!(extract_first_and_last("7pqrstsixteen"), Some((7, 6)));
+        assert_eq!(extract_first_and_last("oneight"), Some((1, 8)));
+    }
+
+    #[test]
+    fn test_missing_and_unicode() {
+        assert_eq!(extract_first_and_last("no digits here"), None);
+        assert_eq!(extract("one", &Vocabulary::digits()), None);
+        assert_eq!(extract_first_and_last("ñandú→seven☃"), Some((7, 7)));
+        assert_eq!(extract_first_and_last("é1ü"), Some((1, 1)));
+        assert_eq!(
+            part_two("1abc2\nnothing\ntwo1nine".as_bytes()).unwrap(),
+            12 + 29
+        );
+    }
+
+    #[test]
+    fn test_diagnose_csv() {
+        let report = diagnose(
+            "two1nine\nabc\nxtwone3four\n1, \"quoted\"",
+            ReportFormat::Csv,
+        );
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(
+            lines[0],
+            "line,p1 first,pos,p1 last,pos,p1,p2 first,pos,p2 last,pos,p2,flags,text"
+        );
+        assert_eq!(lines[1], "1,1,3,1,3,11,two,0,nine,4,29,differs,two1nine");
+        assert_eq!(lines[2], "2,-,-,-,-,-,-,-,-,-,-,no digits,abc");
+        assert_eq!(lines[3], "3,3,6,3,6,33,two,1,four,7,24,differs,xtwone3four");
+        assert_eq!(lines[4], "4,1,0,1,0,11,1,0,1,0,11,,\"1, \"\"quoted\"\"\"");
+    }
+
+    #[test]
+    fn test_diagnose_table() {
+        let report = diagnose("one\n12", ReportFormat::Table);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines.len(), 3);
+        // every column lines up under the header
+        let divider = lines[0].find("| p2 first").unwrap();
+        assert!(lines.iter().all(|line| &line[divider..divider + 1] == "|"));
+        assert!(lines[1].contains("words only"));
+        assert!(lines[2].ends_with("| 12"));
+    }
+
+    #[test]
+    fn test_find_all_overlapping() {
+        // `she` and `he` end together, and `hers` runs on past both
+        let vocabulary = Vocabulary::new([("he", 1), ("she", 2), ("his", 3), ("hers", 4)]);
+        let mut found: Vec<_> = vocabulary
+            .find_all("ushers")
+            .into_iter()
+            .map(|token| (token.start, token.value))
+            .collect();
+        found.sort();
+        assert_eq!(found, vec![(1, 2), (2, 1), (2, 4)]);
+    }
+
+    #[test]
+    fn test_custom_vocabulary() {
+        let german: Vocabulary = "eins 1\nzwei 2\ndrei 3\nzehn 10\n\nnull 0".parse().unwrap();
+        assert_eq!(extract("nulleinszwei", &german), Some((0, 2)));
+        assert_eq!(extract("xzehnx", &german), Some((10, 10)));
+
+        // longest word wins when two start at the same place, but the last token is still
+        // whichever starts last
+        let roman = Vocabulary::new([("I", 1), ("IV", 4), ("V", 5), ("IX", 9), ("X", 10)]);
+        assert_eq!(extract("aIVb", &roman), Some((4, 5)));
+        assert_eq!(extract("aIXb", &roman), Some((9, 10)));
+        assert_eq!(calibrate("IV\nxx\nVI".as_bytes(), &roman).unwrap(), 45 + 51);
+
+        // token values big enough to overflow a u32 once they're combined
+        let big: Vocabulary = format!("max {}\nhuge 429496730", u32::MAX).parse().unwrap();
+        assert_eq!(
+            calibrate("huge..max\nmax".as_bytes(), &big).unwrap(),
+            4294967300 + u32::MAX as u64 + u32::MAX as u64 * 11
+        );
+
+        assert!("zwei".parse::<Vocabulary>().is_err());
+        assert!("zwei two".parse::<Vocabulary>().is_err());
+    }
+
+    #[test]
+    fn test_streaming_input() {
+        // CRLF endings, a line of invalid UTF-8, and no trailing newline
+        let input: &[u8] = b"1abc2\r\n\xff\xfe7\xffx\r\npqr3stu8vwx";
+        assert_eq!(part_one(input).unwrap(), 12 + 77 + 38);
+        // a tiny buffer still only ever hands us whole lines
+        let reader = BufReader::with_capacity(2, input);
+        assert_eq!(part_one(reader).unwrap(), 12 + 77 + 38);
+    }
+
+    #[test]
+    fn test_parallel_matches_sequential() {
+        let mut input = fs::read("input.txt").expect("Expected to load puzzle input");
+        input.push(b'\n');
+        let vocabulary = Vocabulary::spelled_digits();
+        let expected = calibrate(input.as_slice(), &vocabulary).unwrap();
+        // repeat the (newline terminated) input so it spans a few chunks
+        let repeated = input.repeat(10);
+        for threads in [1, 3, 8] {
+            assert_eq!(
+                calibrate_parallel(repeated.as_slice(), &vocabulary, threads).unwrap(),
+                expected * 10
+            );
+        }
+        assert_eq!(calibrate_parallel(&b""[..], &vocabulary, 4).unwrap(), 0);
     }
 }
@@ -1,13 +1,17 @@
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::cmp::max;
+use std::cmp::{max, Reverse};
+use std::collections::{BTreeSet, HashMap};
 use std::{fs, str::FromStr};
 
-#[derive(Default, Debug)]
-struct Drawing {
-    pub red: u32,
-    pub green: u32,
-    pub blue: u32,
+/// the number of cubes of each color pulled out in a single drawing
+#[derive(Default, Debug, PartialEq)]
+struct Drawing(HashMap<String, u32>);
+
+impl Drawing {
+    fn count(&self, color: &str) -> u32 {
+        self.0.get(color).copied().unwrap_or(0)
+    }
 }
 
 impl FromStr for Drawing {
@@ -17,24 +21,24 @@ impl FromStr for Drawing {
         // create our regex
         lazy_static! {
             static ref RE: Regex =
-                Regex::new(r"^\s*(?P<amount>[0-9]+)\s(?P<color>red|green|blue)\s*$").unwrap();
+                Regex::new(r"^\s*(?P<amount>[0-9]+)\s+(?P<color>[a-z]+)\s*$").unwrap();
         }
 
-        // split at ',' and get the amount and color
+        // split at ',' and get the amount and color, any fragment that doesn't look like
+        // "<amount> <color>" is an error rather than something to skip
         let mut drawing = Self::default();
-        for capture in s.split(',').filter_map(|to_match| RE.captures(to_match)) {
+        for fragment in s.split(',') {
+            let capture = RE
+                .captures(fragment)
+                .ok_or_else(|| format!("Couldn't parse fragment '{}'", fragment.trim()))?;
             let count = capture
                 .name("amount")
                 .unwrap()
                 .as_str()
                 .parse::<u32>()
-                .map_err(|_| "Couldn't parse amount")?;
-            match capture.name("color").unwrap().as_str() {
-                "red" => drawing.red += count,
-                "blue" => drawing.blue += count,
-                "green" => drawing.green += count,
-                _ => return Err("Couldn't Match color".into()),
-            }
+                .map_err(|_| format!("Couldn't parse amount in '{}'", fragment.trim()))?;
+            let color = capture.name("color").unwrap().as_str();
+            *drawing.0.entry(color.to_string()).or_default() += count;
         }
 
         Ok(drawing)
@@ -42,44 +46,389 @@ impl FromStr for Drawing {
 }
 
 #[derive(Debug)]
-struct Round(Vec<Drawing>);
+struct Round {
+    pub id: usize,
+    pub drawings: Vec<Drawing>,
+}
 
 impl FromStr for Round {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let drawings = s.split(':').last().unwrap().split(';');
-        let typed = drawings
-            .map(|drawing| Drawing::from_str(drawing))
+        let (header, drawings) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Missing ':' after game id in '{}'", s))?;
+        let id = header
+            .trim()
+            .strip_prefix("Game")
+            .filter(|id| id.starts_with(char::is_whitespace))
+            .ok_or_else(|| format!("Expected 'Game <id>' but found '{}'", header.trim()))?
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| format!("Couldn't parse game id in '{}'", header.trim()))?;
+        let drawings = drawings
+            .split(';')
+            .map(Drawing::from_str)
             .collect::<Result<Vec<_>, _>>()?;
-        Ok(Round(typed))
+        Ok(Round { id, drawings })
+    }
+}
+
+/// the cubes that were loaded into the bag before a game, any color not listed has none
+#[derive(Debug)]
+struct Bag(HashMap<String, u32>);
+
+impl Bag {
+    /// the bag from the puzzle description
+    fn puzzle() -> Self {
+        "12 red, 13 green, 14 blue".parse().unwrap()
+    }
+
+    fn limit(&self, color: &str) -> u32 {
+        self.0.get(color).copied().unwrap_or(0)
+    }
+}
+
+impl FromStr for Bag {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // a bag is written just like a drawing, e.g. "12 red, 13 green, 14 blue"
+        Ok(Bag(Drawing::from_str(s)?.0))
+    }
+}
+
+/// a single color in a single drawing that pulled out more cubes than the bag holds
+#[derive(Debug, PartialEq)]
+struct Violation {
+    pub drawing: usize,
+    pub color: String,
+    pub drawn: u32,
+    pub limit: u32,
+}
+
+impl Round {
+    fn violations(&self, bag: &Bag) -> Vec<Violation> {
+        let mut violations: Vec<_> = self
+            .drawings
+            .iter()
+            .enumerate()
+            .flat_map(|(drawing, cubes)| {
+                cubes
+                    .0
+                    .iter()
+                    .filter(|(color, &drawn)| drawn > bag.limit(color))
+                    .map(move |(color, &drawn)| Violation {
+                        drawing,
+                        color: color.clone(),
+                        drawn,
+                        limit: bag.limit(color),
+                    })
+            })
+            .collect();
+        // the map doesn't keep colors in order, so sort to keep reports stable
+        violations.sort_by(|a, b| (a.drawing, &a.color).cmp(&(b.drawing, &b.color)));
+        violations
+    }
+
+    fn is_possible_with(&self, bag: &Bag) -> bool {
+        minimum_cube_count(self)
+            .0
+            .iter()
+            .all(|(color, &count)| count <= bag.limit(color))
     }
 }
 
+fn impossible_rounds(input: &str, bag: &Bag) -> Vec<(usize, Vec<Violation>)> {
+    input
+        .lines()
+        .map(|line| Round::from_str(line).expect("could not parse line"))
+        .filter(|round| !round.is_possible_with(bag))
+        .map(|round| (round.id, round.violations(bag)))
+        .collect()
+}
+
 fn minimum_cube_count(round: &Round) -> Drawing {
     round
-        .0
+        .drawings
         .iter()
-        .fold(Drawing::default(), |min_drawing, current_drawing| Drawing {
-            red: max(min_drawing.red, current_drawing.red),
-            green: max(min_drawing.green, current_drawing.green),
-            blue: max(min_drawing.blue, current_drawing.blue),
+        .fold(Drawing::default(), |mut min_drawing, current_drawing| {
+            for (color, &count) in &current_drawing.0 {
+                let min_count = min_drawing.0.entry(color.clone()).or_default();
+                *min_count = max(*min_count, count);
+            }
+            min_drawing
         })
 }
 
-fn part_one(input: &str) -> usize {
-    input
-        .lines()
-        .map(|line| Round::from_str(line).expect("could not parse line"))
-        .map(|round| minimum_cube_count(&round))
-        .enumerate()
-        .filter_map(|(i, count)| {
-            if count.red <= 12 && count.green <= 13 && count.blue <= 14 {
-                Some(i + 1)
+fn covering_drawing(drawings: Vec<Drawing>) -> Drawing {
+    // the smallest set of cubes that could have produced every one of these drawings
+    minimum_cube_count(&Round { id: 0, drawings })
+}
+
+fn covering_bag(rounds: &[Round]) -> Bag {
+    // the smallest bag that makes every game possible
+    Bag(covering_drawing(rounds.iter().map(minimum_cube_count).collect()).0)
+}
+
+fn games_needed(games: usize, fraction: f64) -> usize {
+    // how many games make up at least `fraction` of them. Nudge down before rounding up so
+    // float noise like 0.07 * 100.0 = 7.000000000000001 doesn't ask for an extra game
+    (fraction.clamp(0.0, 1.0) * games as f64 - 1e-9)
+        .ceil()
+        .max(0.0) as usize
+}
+
+// how far past the covering bag the likelihood search will grow the bag
+const MAX_BAG_SCALE: u32 = 10;
+
+fn likelihood_bag(rounds: &[Round]) -> (Bag, bool) {
+    // assumes each drawing is a handful of cubes pulled out of the bag together, without
+    // replacement, and put back before the next drawing. A drawing of k_c cubes of each color
+    // (n in all) from a bag holding K_c of them (N in all) then has probability
+    //   prod C(K_c, k_c) / C(N, n)
+    // for a fixed N each color's part of the log likelihood is concave in K_c, so the best bag
+    // of each size is the best one a cube smaller plus whichever cube helps the most. Grow the
+    // bag that way from the covering bag, the smallest with any likelihood at all, and keep
+    // the most likely size. Drawings that look like they were made with replacement keep
+    // getting more likely as the bag grows, so stop at MAX_BAG_SCALE times the covering bag
+    // and also return whether the best bag was the biggest one tried
+    let drawings: Vec<_> = rounds.iter().flat_map(|round| &round.drawings).collect();
+    let covering = covering_bag(rounds);
+    let colors: Vec<_> = BTreeSet::from_iter(covering.0.keys().cloned())
+        .into_iter()
+        .collect();
+    // lots of drawings pull out the same number of cubes, so count how often each one comes
+    // up instead of going through every drawing at every step
+    let histogram = |counts: Vec<u32>| {
+        let mut histogram = HashMap::<u32, u32>::new();
+        for count in counts.into_iter().filter(|&count| count > 0) {
+            *histogram.entry(count).or_default() += 1;
+        }
+        histogram.into_iter().collect::<Vec<_>>()
+    };
+    let sizes = histogram(
+        drawings
+            .iter()
+            .map(|drawing| drawing.0.values().sum())
+            .collect(),
+    );
+    let drawn: Vec<_> = colors
+        .iter()
+        .map(|color| {
+            histogram(
+                drawings
+                    .iter()
+                    .map(|drawing| drawing.count(color))
+                    .collect(),
+            )
+        })
+        .collect();
+    // ln C(K + 1, k) - ln C(K, k) for every drawing of k, how much more likely they all get
+    // with one more cube
+    let growth = |cubes: u32, drawn: &[(u32, u32)]| -> f64 {
+        drawn
+            .iter()
+            .map(|&(k, times)| times as f64 * ((cubes + 1) as f64 / (cubes + 1 - k) as f64).ln())
+            .sum()
+    };
+
+    let mut limits: Vec<u32> = colors.iter().map(|color| covering.limit(color)).collect();
+    let mut total: u32 = limits.iter().sum();
+    let largest = total * MAX_BAG_SCALE;
+    // the log likelihood relative to the covering bag
+    let mut likelihood = 0.0;
+    let mut best = (likelihood, limits.clone(), total);
+    while total < largest {
+        // ties go to the first color alphabetically
+        let Some((color, gain)) = (0..colors.len()).fold(None, |best, color| {
+            let gain = growth(limits[color], &drawn[color]);
+            match best {
+                Some((_, best_gain)) if best_gain >= gain => best,
+                _ => Some((color, gain)),
+            }
+        }) else {
+            break;
+        };
+        likelihood += gain - growth(total, &sizes);
+        limits[color] += 1;
+        total += 1;
+        // a little slack so a plateau of equally likely bags keeps the smallest
+        if likelihood > best.0 + 1e-9 {
+            best = (likelihood, limits.clone(), total);
+        }
+    }
+    let (_, limits, size) = best;
+    let bag = Bag(colors.into_iter().zip(limits).collect());
+    (bag, size == largest && size > 0)
+}
+
+// how many times the exact search checks a game against a limit before settling for the best
+// bag it's found so far
+const SEARCH_BUDGET: usize = 5_000_000;
+
+fn smallest_bag(rounds: &[Round], fraction: f64) -> (Bag, bool) {
+    // the smallest bag for some set of games covers exactly what they needed, so every limit
+    // in it is what one of the games needed of that color. Searching each combination of
+    // those grows exponentially with the number of colors, so start from a greedy guess and
+    // only search within a budget. Also returns whether the bag is known to be the smallest
+    let keep = games_needed(rounds.len(), fraction);
+    let minimums: Vec<_> = rounds.iter().map(minimum_cube_count).collect();
+    let colors: Vec<_> = BTreeSet::from_iter(minimums.iter().flat_map(|drawing| drawing.0.keys()))
+        .into_iter()
+        .collect();
+    // what each game needed of each color, in the same order as `colors`
+    let needs: Vec<Vec<u32>> = minimums
+        .iter()
+        .map(|drawing| colors.iter().map(|color| drawing.count(color)).collect())
+        .collect();
+    let greedy = greedy_bag(&needs, colors.len(), keep);
+    let mut best = (greedy.iter().sum(), greedy);
+    let mut budget = SEARCH_BUDGET;
+    search_bags(
+        needs.iter().map(Vec::as_slice).collect(),
+        colors.len(),
+        keep,
+        &mut Vec::new(),
+        0,
+        &mut best,
+        &mut budget,
+    );
+    let bag = Bag(colors
+        .into_iter()
+        .cloned()
+        .zip(best.1)
+        .filter(|&(_, limit)| limit > 0)
+        .collect());
+    (bag, budget > 0)
+}
+
+fn greedy_bag(needs: &[Vec<u32>], colors: usize, keep: usize) -> Vec<u32> {
+    // keep dropping whichever game shrinks the bag the most. A game only shrinks a color if it
+    // needed strictly more of it than any other game left, and then by the gap to the runner up
+    let mut kept: Vec<&[u32]> = needs.iter().map(Vec::as_slice).collect();
+    let top_two = |kept: &[&[u32]], color: usize| {
+        kept.iter().fold((0, 0), |(first, second), game| {
+            if game[color] > first {
+                (game[color], first)
             } else {
-                None
+                (first, second.max(game[color]))
             }
         })
+    };
+    while kept.len() > keep {
+        let tops: Vec<_> = (0..colors).map(|color| top_two(&kept, color)).collect();
+        let drop = (0..kept.len())
+            .max_by_key(|&i| {
+                let saved: u32 = tops
+                    .iter()
+                    .enumerate()
+                    .filter(|&(color, &(first, _))| kept[i][color] == first)
+                    .map(|(_, (first, second))| first - second)
+                    .sum();
+                // ties go to the earliest game
+                (saved, Reverse(i))
+            })
+            .unwrap();
+        kept.remove(drop);
+    }
+    (0..colors).map(|color| top_two(&kept, color).0).collect()
+}
+
+fn search_bags(
+    games: Vec<&[u32]>,
+    colors: usize,
+    keep: usize,
+    limits: &mut Vec<u32>,
+    total: u32,
+    best: &mut (u32, Vec<u32>),
+    budget: &mut usize,
+) {
+    // pick a limit for one color at a time, giving up on a branch as soon as it rules out too
+    // many games or can't beat the best bag so far, or on everything once the budget's spent
+    if *budget == 0 || games.len() < keep || total >= best.0 {
+        return;
+    }
+    let color = limits.len();
+    if color == colors {
+        *best = (total, limits.clone());
+        return;
+    }
+    let mut candidates: Vec<u32> = games.iter().map(|game| game[color]).collect();
+    candidates.push(0);
+    candidates.sort_unstable();
+    candidates.dedup();
+    for limit in candidates {
+        let Some(left) = budget.checked_sub(games.len()) else {
+            *budget = 0;
+            return;
+        };
+        *budget = left;
+        let possible = games
+            .iter()
+            .copied()
+            .filter(|game| game[color] <= limit)
+            .collect();
+        limits.push(limit);
+        search_bags(possible, colors, keep, limits, total + limit, best, budget);
+        limits.pop();
+    }
+}
+
+fn statistics(rounds: &[Round], fraction: f64) -> String {
+    let drawings: Vec<_> = rounds.iter().flat_map(|round| &round.drawings).collect();
+    let (likely, capped) = likelihood_bag(rounds);
+    let (smallest, exact) = smallest_bag(rounds, fraction);
+    let colors: BTreeSet<_> = drawings
+        .iter()
+        .flat_map(|drawing| drawing.0.keys())
+        .collect();
+
+    let mut table = format!(
+        "{} games, {} drawings\n{:<10} {:>6} {:>8} {:>10} {:>10}\n",
+        rounds.len(),
+        drawings.len(),
+        "color",
+        "max",
+        "mean",
+        "ml bag",
+        format!("{:.0}% bag", fraction * 100.0)
+    );
+    for color in colors {
+        // the mean is over every drawing, a drawing without the color pulled out none of it
+        let counts = drawings.iter().map(|drawing| drawing.count(color));
+        let max = counts.clone().max().unwrap_or(0);
+        let mean = counts.sum::<u32>() as f64 / drawings.len() as f64;
+        table += &format!(
+            "{:<10} {:>6} {:>8.2} {:>10} {:>10}\n",
+            color,
+            max,
+            mean,
+            likely.limit(color),
+            smallest.limit(color)
+        );
+    }
+    if capped {
+        table += &format!(
+            "ml bag is still getting more likely at {} cubes, where the search stops\n",
+            likely.0.values().sum::<u32>()
+        );
+    }
+    if !exact {
+        table += &format!(
+            "{:.0}% bag is approximate, the search gave up before proving it's the smallest\n",
+            fraction * 100.0
+        );
+    }
+    table
+}
+
+fn part_one(input: &str, bag: &Bag) -> usize {
+    input
+        .lines()
+        .map(|line| Round::from_str(line).expect("could not parse line"))
+        .filter(|round| round.is_possible_with(bag))
+        .map(|round| round.id)
         .sum()
 }
 
@@ -88,15 +437,45 @@ fn part_two(input: &str) -> u32 {
         .lines()
         .map(|line| Round::from_str(line).expect("could not parse line"))
         .map(|round| minimum_cube_count(&round))
-        .map(|count| count.red * count.blue * count.green)
+        .map(|count| count.count("red") * count.count("blue") * count.count("green"))
         .sum()
 }
 
 fn main() {
     let input = fs::read_to_string("rust/day_02/input.txt").expect("Expected to load puzzle input");
 
-    println!("Part One Solution: {}", part_one(&input));
+    let bag = match std::env::args().skip_while(|arg| arg != "--bag").nth(1) {
+        Some(contents) => contents
+            .parse()
+            .expect("Expected bag contents like '12 red, 13 green'"),
+        None => Bag::puzzle(),
+    };
+
+    println!("Part One Solution: {}", part_one(&input, &bag));
     println!("Part Two Solution: {}", part_two(&input));
+
+    if let Some(fraction) = std::env::args().skip_while(|arg| arg != "--stats").nth(1) {
+        let fraction = fraction.parse().expect("Expected a fraction of games");
+        let rounds: Vec<_> = input
+            .lines()
+            .map(|line| Round::from_str(line).expect("could not parse line"))
+            .collect();
+        print!("{}", statistics(&rounds, fraction));
+    }
+    if std::env::args().any(|arg| arg == "--impossible") {
+        for (id, violations) in impossible_rounds(&input, &bag) {
+            println!("Game {} is impossible:", id);
+            for violation in violations {
+                println!(
+                    "  drawing {} pulled {} {} but the bag only holds {}",
+                    violation.drawing + 1,
+                    violation.drawn,
+                    violation.color,
+                    violation.limit
+                );
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -107,6 +486,311 @@ mod test {
     fn test_min_cube_count() {
         let round = Round::from_str("Game 8: 2 blue, 12 red; 1 green, 2 blue, 10 red; 12 red, 10 blue; 5 red, 1 green, 2 blue; 13 red, 16 blue, 1 green; 2 blue, 18 red").unwrap();
         let min = minimum_cube_count(&round);
-        assert_eq!(min.red, 18);
+        assert_eq!(min.count("red"), 18);
+    }
+
+    #[test]
+    fn test_arbitrary_colors() {
+        let drawing = Drawing::from_str(" 3 purple, 2 red, 1 purple").unwrap();
+        assert_eq!(drawing.count("purple"), 4);
+        assert_eq!(drawing.count("red"), 2);
+        assert_eq!(drawing.count("blue"), 0);
+
+        let round = Round::from_str("Game 1: 3 teal; 7 teal, 1 red").unwrap();
+        assert_eq!(minimum_cube_count(&round).count("teal"), 7);
+
+        // bad fragments are reported instead of being dropped
+        assert!(Drawing::from_str("3 blue, three red")
+            .unwrap_err()
+            .contains("three red"));
+        assert!(Drawing::from_str("3 blue,").is_err());
+        assert!(Round::from_str("Game 2: 1 red; 4 green blue").is_err());
+    }
+
+    #[test]
+    fn test_game_ids() {
+        assert_eq!(Round::from_str("Game 42: 1 red").unwrap().id, 42);
+        assert_eq!(Round::from_str("  Game   7 : 1 red").unwrap().id, 7);
+        assert!(Round::from_str("1 red, 2 blue").is_err());
+        assert!(Round::from_str(": 1 red").is_err());
+        assert!(Round::from_str("Game: 1 red").is_err());
+        assert!(Round::from_str("Game12: 1 red").is_err());
+        assert!(Round::from_str("Game x: 1 red").is_err());
+        assert!(Round::from_str("Match 3: 1 red").is_err());
+
+        // ids come from the input, not the line number
+        let input = "Game 5: 3 red\nGame 2: 20 red\nGame 10: 1 blue";
+        assert_eq!(part_one(input, &Bag::puzzle()), 5 + 10);
+    }
+
+    #[test]
+    fn test_bag_feasibility() {
+        let round = Round::from_str(
+            "Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red",
+        )
+        .unwrap();
+        assert!(!round.is_possible_with(&Bag::puzzle()));
+        assert!(round.is_possible_with(&"20 red, 13 green, 6 blue".parse().unwrap()));
+        // colors missing from the bag can't be drawn at all
+        assert!(!round.is_possible_with(&"20 red, 13 green".parse().unwrap()));
+
+        assert_eq!(
+            round.violations(&"4 red, 13 green, 5 blue".parse().unwrap()),
+            vec![
+                Violation {
+                    drawing: 0,
+                    color: "blue".into(),
+                    drawn: 6,
+                    limit: 5
+                },
+                Violation {
+                    drawing: 0,
+                    color: "red".into(),
+                    drawn: 20,
+                    limit: 4
+                },
+            ]
+        );
+
+        let input = "Game 1: 3 red\nGame 2: 20 red; 15 blue\nGame 4: 1 gold";
+        let impossible = impossible_rounds(input, &Bag::puzzle());
+        let ids: Vec<_> = impossible.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec![2, 4]);
+        assert_eq!(impossible[0].1.len(), 2);
+        assert_eq!(impossible[1].1[0].color, "gold");
+        assert!("12 red, lots of blue".parse::<Bag>().is_err());
+    }
+
+    #[test]
+    fn test_statistics() {
+        let rounds: Vec<_> = [
+            "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green",
+            "Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue",
+            "Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red",
+            "Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red",
+            "Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green",
+        ]
+        .iter()
+        .map(|line| Round::from_str(line).unwrap())
+        .collect();
+
+        let covering = covering_bag(&rounds);
+        assert_eq!(
+            (
+                covering.limit("red"),
+                covering.limit("green"),
+                covering.limit("blue")
+            ),
+            (20, 13, 15)
+        );
+
+        // every game possible is the covering bag, dropping one game drops game 3 or 4
+        assert_eq!(smallest_bag(&rounds, 1.0).0 .0, covering.0);
+        let (smallest, exact) = smallest_bag(&rounds, 0.8);
+        assert!(exact);
+        assert_eq!(
+            (
+                smallest.limit("red"),
+                smallest.limit("green"),
+                smallest.limit("blue")
+            ),
+            (14, 3, 15)
+        );
+        let possible = rounds
+            .iter()
+            .filter(|round| round.is_possible_with(&smallest))
+            .count();
+        assert_eq!(possible, 4);
+        assert_eq!(smallest_bag(&rounds, 0.0).0 .0.len(), 0);
+
+        let table = statistics(&rounds, 0.8);
+        assert!(table.starts_with("5 games, 14 drawings\n"));
+        assert_eq!(
+            table.contains("still getting more likely"),
+            likelihood_bag(&rounds).1
+        );
+        let red = table.lines().find(|line| line.starts_with("red")).unwrap();
+        assert_eq!(
+            red.split_whitespace().collect::<Vec<_>>(),
+            vec![
+                "red".to_string(),
+                "20".to_string(),
+                "4.36".to_string(),
+                likelihood_bag(&rounds).0.limit("red").to_string(),
+                "14".to_string()
+            ]
+        );
+    }
+
+    fn log_likelihood(bag: &Bag, rounds: &[Round]) -> f64 {
+        // straight from the definition, to check the incremental search against
+        let ln_choose = |n: u32, k: u32| -> f64 {
+            (0..k).map(|i| ((n - i) as f64 / (k - i) as f64).ln()).sum()
+        };
+        let total: u32 = bag.0.values().sum();
+        rounds
+            .iter()
+            .flat_map(|round| &round.drawings)
+            .map(|drawing| {
+                let colors: f64 = drawing
+                    .0
+                    .iter()
+                    .map(|(color, &count)| ln_choose(bag.limit(color), count))
+                    .sum();
+                colors - ln_choose(total, drawing.0.values().sum())
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_likelihood_bag() {
+        let round = |drawings: &str| vec![Round::from_str(&format!("Game 1: {drawings}")).unwrap()];
+        let limits = |bag: &Bag| (bag.limit("red"), bag.limit("blue"));
+
+        // three reds and a blue, one at a time, fit a 3:1 bag better than the covering 1:1
+        let (bag, capped) = likelihood_bag(&round("1 red; 1 red; 1 red; 1 blue"));
+        assert_eq!(limits(&bag), (3, 1));
+        assert!(!capped);
+
+        // always pulling out one of each only happens every time with exactly one of each
+        let (bag, capped) = likelihood_bag(&round("1 red, 1 blue; 1 blue, 1 red"));
+        assert_eq!(limits(&bag), (1, 1));
+        assert!(!capped);
+
+        // clumps of one color look like drawing with replacement, so bigger is always better
+        let (bag, capped) = likelihood_bag(&round("5 red; 5 blue"));
+        assert_eq!(bag.0.values().sum::<u32>(), 100);
+        assert!(capped);
+
+        // nothing on a grid of bags beats the search
+        let rounds = round("2 red, 1 blue; 1 red; 3 red; 1 blue, 1 green; 2 red, 1 green");
+        let (bag, capped) = likelihood_bag(&rounds);
+        assert!(!capped);
+        let found = log_likelihood(&bag, &rounds);
+        for red in 3..30 {
+            for blue in 1..30 {
+                for green in 1..30 {
+                    let other = Bag(HashMap::from([
+                        ("red".to_string(), red),
+                        ("blue".to_string(), blue),
+                        ("green".to_string(), green),
+                    ]));
+                    assert!(log_likelihood(&other, &rounds) <= found + 1e-9);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_games_needed() {
+        assert_eq!(games_needed(100, 0.07), 7);
+        assert_eq!(games_needed(100, 0.071), 8);
+        assert_eq!(games_needed(3, 1.0 / 3.0), 1);
+        assert_eq!(games_needed(5, 0.0), 0);
+        assert_eq!(games_needed(5, 1.0), 5);
+        assert_eq!(games_needed(5, 2.0), 5);
+    }
+
+    #[test]
+    fn test_smallest_bag_trade_off() {
+        // the cheapest single game isn't the one needing the fewest of any one color, and
+        // covering two games is cheapest with a pair that shares a big color
+        let rounds: Vec<_> = [
+            "4 red, 2 blue",
+            "8 blue",
+            "9 blue",
+            "3 red, 9 blue",
+            "7 red, 2 blue",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, drawing)| Round::from_str(&format!("Game {}: {}", i + 1, drawing)).unwrap())
+        .collect();
+        let (bag, _) = smallest_bag(&rounds, 0.2);
+        assert_eq!((bag.limit("red"), bag.limit("blue")), (4, 2));
+        // greedy keeps the (0, 8) game, the search has to find its way past that
+        let needs: Vec<_> = rounds
+            .iter()
+            .map(minimum_cube_count)
+            .map(|game| vec![game.count("blue"), game.count("red")])
+            .collect();
+        assert_eq!(greedy_bag(&needs, 2, 1), vec![8, 0]);
+
+        // check every fraction against trying every set of games
+        for keep in 0..=rounds.len() {
+            let fraction = keep as f64 / rounds.len() as f64;
+            let best = (0..1u32 << rounds.len())
+                .filter(|set| set.count_ones() as usize >= keep)
+                .map(|set| {
+                    let kept = rounds
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| set & (1 << i) != 0)
+                        .map(|(_, round)| minimum_cube_count(round))
+                        .collect();
+                    covering_drawing(kept).0.values().sum::<u32>()
+                })
+                .min()
+                .unwrap();
+            let (bag, exact) = smallest_bag(&rounds, fraction);
+            assert!(exact);
+            assert_eq!(bag.0.values().sum::<u32>(), best);
+            let possible = rounds
+                .iter()
+                .filter(|round| round.is_possible_with(&bag))
+                .count();
+            assert!(possible >= keep);
+        }
+    }
+
+    #[test]
+    fn test_smallest_bag_many_colors() {
+        // 200 games drawing from a dozen colors, far too many combinations to search them all
+        let colors = [
+            "red", "orange", "yellow", "green", "blue", "indigo", "violet", "black", "white",
+            "grey", "pink", "brown",
+        ];
+        let mut seed: u64 = 2023;
+        let mut random = |below: u64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) % below
+        };
+        let input: Vec<String> = (1..=200)
+            .map(|id| {
+                let drawings: Vec<String> = (0..3)
+                    .map(|_| {
+                        let first = random(colors.len() as u64) as usize;
+                        let cubes: Vec<String> = (0..6)
+                            .map(|i| {
+                                let color = colors[(first + i) % colors.len()];
+                                format!("{} {}", random(20) + 1, color)
+                            })
+                            .collect();
+                        cubes.join(", ")
+                    })
+                    .collect();
+                format!("Game {}: {}", id, drawings.join("; "))
+            })
+            .collect();
+        let rounds: Vec<_> = input
+            .iter()
+            .map(|line| Round::from_str(line).unwrap())
+            .collect();
+
+        for fraction in [0.2, 0.5] {
+            let (bag, exact) = smallest_bag(&rounds, fraction);
+            let possible = rounds
+                .iter()
+                .filter(|round| round.is_possible_with(&bag))
+                .count();
+            assert!(possible >= games_needed(rounds.len(), fraction));
+            // never worse than the covering bag, and the table owns up to the search giving up
+            assert!(bag.0.values().sum::<u32>() <= covering_bag(&rounds).0.values().sum());
+            let table = statistics(&rounds, fraction);
+            assert!(!exact);
+            assert!(table.contains("approximate"));
+        }
     }
 }
@@ -1,4 +1,3 @@
-use itertools::{Itertools, Tuples};
 use pest::Parser;
 use pest_derive::Parser;
 use std::collections::{HashMap, HashSet, VecDeque};
@@ -45,18 +44,79 @@ impl Cycle {
     }
 }
 
+/// Problems with the network itself, found while loading it
+#[derive(Debug, PartialEq, Eq)]
+pub enum NetworkError {
+    Parse(String),
+    // every (source, target) pair where the target has no entry of its own
+    UndefinedTargets(Vec<(NodeId, NodeId)>),
+}
+
+impl std::fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetworkError::Parse(err) => write!(f, "couldn't parse the network:\n{err}"),
+            NetworkError::UndefinedTargets(targets) => {
+                let targets: Vec<_> = targets
+                    .iter()
+                    .map(|(source, target)| format!("{source} -> {target}"))
+                    .collect();
+                write!(
+                    f,
+                    "nodes point at targets that aren't in the network: {}",
+                    targets.join(", ")
+                )
+            }
+        }
+    }
+}
+
+/// Reasons a walk from a given start can never finish
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Unsolvable {
+    MissingStart(NodeId),
+    // no goal node is connected to the start at all
+    UnreachableGoal(NodeId),
+    // goals exist in the graph, but following the route never lands on one
+    NoExitInCycle(NodeId),
+}
+
+impl std::fmt::Display for Unsolvable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Unsolvable::MissingStart(node) => write!(f, "start node {node} is not in the network"),
+            Unsolvable::UnreachableGoal(node) => write!(f, "no goal is reachable from {node}"),
+            Unsolvable::NoExitInCycle(node) => {
+                write!(f, "the route from {node} cycles without reaching a goal")
+            }
+        }
+    }
+}
+
+/// Which walks through the network can finish, worked out once when it's loaded
+#[derive(Debug, Default)]
+pub struct Validation {
+    // why the AAA -> ZZZ walk can't finish, if it can't
+    part_one: Option<Unsolvable>,
+    // every ..A start whose walk never lands on a ..Z node
+    part_two: Vec<Unsolvable>,
+    // the cycle of every ..A start, kept so the solver doesn't walk them again
+    cycles: HashMap<NodeId, Cycle>,
+}
+
 #[derive(Debug)]
 pub struct Input {
     route: String,
     mapping: HashMap<String, Node>,
+    validation: Validation,
 }
 
 impl FromStr for Input {
-    type Err = ();
+    type Err = NetworkError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let parsed = PuzzleParser::parse(Rule::input, s)
-            .expect("Expected to Parse")
+            .map_err(|err| NetworkError::Parse(err.to_string()))?
             .next()
             .unwrap();
         let mut pairs = parsed.into_inner();
@@ -77,11 +137,101 @@ impl FromStr for Input {
                 (source, Node { left, right })
             });
         let mapping = HashMap::from_iter(mappings);
-        Ok(Self { route, mapping })
+        let mut input = Self {
+            route,
+            mapping,
+            validation: Validation::default(),
+        };
+        input.validate()?;
+        input.validation = input.check_starts();
+        Ok(input)
     }
 }
 
 impl Input {
+    fn validate(&self) -> Result<(), NetworkError> {
+        // every left/right target needs its own entry, otherwise walking the map panics
+        let mut undefined: Vec<_> = self
+            .mapping
+            .iter()
+            .flat_map(|(source, node)| [(source, &node.left), (source, &node.right)])
+            .filter(|(_, target)| !self.mapping.contains_key(*target))
+            .map(|(source, target)| (source.clone(), target.clone()))
+            .collect();
+        if undefined.is_empty() {
+            Ok(())
+        } else {
+            undefined.sort();
+            Err(NetworkError::UndefinedTargets(undefined))
+        }
+    }
+
+    pub fn reachable(&self, from: &str) -> HashSet<&str> {
+        // every node connected to `from` by any sequence of left/right turns
+        let mut seen = HashSet::new();
+        let mut to_visit = VecDeque::new();
+        if let Some((node, _)) = self.mapping.get_key_value(from) {
+            to_visit.push_back(node.as_str());
+        }
+        while let Some(node) = to_visit.pop_front() {
+            if seen.insert(node) {
+                let paths = &self.mapping[node];
+                to_visit.push_back(&paths.left);
+                to_visit.push_back(&paths.right);
+            }
+        }
+        seen
+    }
+
+    pub fn check_start(
+        &self,
+        start: &str,
+        is_goal: impl Fn(&str) -> bool,
+    ) -> Result<Cycle, Unsolvable> {
+        if !self.mapping.contains_key(start) {
+            return Err(Unsolvable::MissingStart(start.into()));
+        }
+        if !self.reachable(start).into_iter().any(&is_goal) {
+            return Err(Unsolvable::UnreachableGoal(start.into()));
+        }
+        let (exits, cycle) = self.first_lap(start, is_goal);
+        if exits.is_empty() {
+            return Err(Unsolvable::NoExitInCycle(start.into()));
+        }
+        Ok(cycle)
+    }
+
+    fn check_starts(&self) -> Validation {
+        let part_one = self.check_start("AAA", |node| node == "ZZZ").err();
+
+        let mut starts: Vec<_> = self.mapping.keys().filter(|x| x.ends_with('A')).collect();
+        starts.sort();
+        let mut part_two = Vec::new();
+        let mut cycles = HashMap::new();
+        for start in starts {
+            match self.check_start(start, |node| node.ends_with('Z')) {
+                // all the starts have to line up on an exit together, which only happens
+                // inside their cycles
+                Ok(cycle) if cycle.exits.is_empty() => {
+                    part_two.push(Unsolvable::NoExitInCycle(start.clone()))
+                }
+                Ok(cycle) => {
+                    cycles.insert(start.clone(), cycle);
+                }
+                Err(problem) => part_two.push(problem),
+            }
+        }
+        Validation {
+            part_one,
+            part_two,
+            cycles,
+        }
+    }
+
+    pub fn unsolvable_starts(&self) -> &[Unsolvable] {
+        &self.validation.part_two
+    }
+
     pub fn cycle(&self, from: &str) -> String {
         // go through a full round of input
         self.route
@@ -100,34 +250,51 @@ impl Input {
             .into()
     }
 
-    pub fn find_cycle(&self, start: &str) -> Cycle {
+    fn cycle_bounds(&self, start: &str) -> (usize, usize) {
+        // returns how many full routes we walk before entering the cycle, and how many
+        // full routes the cycle itself takes
         let mut seen_starts: HashMap<String, usize> = HashMap::new();
         let mut start: String = start.into();
         let mut iterations = 0;
-        while seen_starts.get(&start) == None {
+        while !seen_starts.contains_key(&start) {
             let next = self.cycle(&start);
             seen_starts.insert(start, iterations);
             iterations += 1;
             start = next;
         }
-        let cycle_start = seen_starts.get(&start).unwrap();
-        let cycle_length = (iterations - cycle_start) * self.route.len();
-        let exits = HashSet::from_iter(
-            self.find_exits(&start, cycle_length)
-                .into_iter()
-                .map(|x| x + cycle_start * self.route.len()),
-        );
-        Cycle {
-            exits,
-            size: (iterations - cycle_start) * self.route.len(),
-        }
+        let cycle_start = seen_starts[&start];
+        (cycle_start, iterations - cycle_start)
+    }
+
+    pub fn find_cycle(&self, start: &str) -> Cycle {
+        self.first_lap(start, |node| node.ends_with('Z')).1
+    }
+
+    fn first_lap(&self, start: &str, is_goal: impl Fn(&str) -> bool) -> (Vec<usize>, Cycle) {
+        // walks up to the cycle and once around it, returning every exit along the way and the
+        // cycle with just the exits inside it. The walk is periodic from there on, so if we
+        // haven't hit a goal by the end of the lap we never will
+        let (cycle_start, cycle_iterations) = self.cycle_bounds(start);
+        let lead_in = cycle_start * self.route.len();
+        let cycle_length = cycle_iterations * self.route.len();
+        let exits = self.walk_exits(start, lead_in + cycle_length, is_goal);
+        let cycle = Cycle {
+            exits: exits.iter().copied().filter(|&x| x >= lead_in).collect(),
+            size: cycle_length,
+        };
+        (exits, cycle)
     }
 
-    pub fn find_exits(&self, start: &str, max_iterations: usize) -> Vec<usize> {
+    fn walk_exits(
+        &self,
+        start: &str,
+        max_iterations: usize,
+        is_goal: impl Fn(&str) -> bool,
+    ) -> Vec<usize> {
         let mut exits = Vec::new();
         let mut node: &String = &start.into();
         for step in 0..max_iterations {
-            if node.ends_with('Z') {
+            if is_goal(node) {
                 exits.push(step);
             }
             let direction = self.route.as_bytes()[step % self.route.len()] as char;
@@ -142,7 +309,10 @@ impl Input {
     }
 }
 
-fn part_one(input: &Input) -> impl std::fmt::Display {
+fn part_one(input: &Input) -> Result<usize, Unsolvable> {
+    if let Some(problem) = &input.validation.part_one {
+        return Err(problem.clone());
+    }
     let mut iterations = 0;
     let mut next: Option<&str> = Some("AAA");
     while let Some(node) = next {
@@ -156,27 +326,25 @@ fn part_one(input: &Input) -> impl std::fmt::Display {
         next = if next_node == "ZZZ" {
             None
         } else {
-            Some(&next_node)
+            Some(next_node)
         };
         iterations += 1;
     }
-    iterations
+    Ok(iterations)
 }
 
-fn part_two(input: &Input) -> usize {
+fn part_two(input: &Input) -> Result<usize, Unsolvable> {
     // kind of annoyed with part two. I thought we would have to do a more general merging of multiple cycles
     // to find the points where they both exit at the same time
 
     // turns out each cycle has one exit, and the exit is the cycle length, so you just take the LCM of them all...
-    let cycles_iter = input
-        .mapping
-        .keys()
-        .filter(|x| x.ends_with('A'))
-        .map(|start| input.find_cycle(start));
+    if let Some(problem) = input.unsolvable_starts().first() {
+        return Err(problem.clone());
+    }
+    let cycles = input.validation.cycles.values();
 
-    let cycles: Vec<_> = cycles_iter.collect();
     // println!("{:?}", cycles);
-    cycles.into_iter().fold(1, |lcm, cycle| num::integer::lcm(lcm, cycle.size))
+    Ok(cycles.fold(1, |lcm, cycle| num::integer::lcm(lcm, cycle.size)))
 
     // This is the code that would have done it the other way but it chugs!
     // need to find a better way to calculate the intersections
@@ -195,10 +363,22 @@ fn part_two(input: &Input) -> usize {
 fn main() {
     let input_str =
         fs::read_to_string("rust/day_08/input.txt").expect("Expected to load puzzle input");
-    let input = Input::from_str(&input_str).expect("Expected to parse");
+    let input = match Input::from_str(&input_str) {
+        Ok(input) => input,
+        Err(err) => {
+            println!("Couldn't load the network: {}", err);
+            return;
+        }
+    };
 
-    println!("Part One Solution: {}", part_one(&input));
-    println!("Part Two Solution: {}", part_two(&input));
+    match part_one(&input) {
+        Ok(soln) => println!("Part One Solution: {}", soln),
+        Err(problem) => println!("Part One Unsolvable: {}", problem),
+    }
+    match part_two(&input) {
+        Ok(soln) => println!("Part Two Solution: {}", soln),
+        Err(problem) => println!("Part Two Unsolvable: {}", problem),
+    }
 }
 
 #[cfg(test)]
@@ -237,6 +417,89 @@ mod test {
         let input_str =
             fs::read_to_string("test_input.txt").expect("Expected to load puzzle input");
         let input = Input::from_str(&input_str).expect("Expected to parse");
-        assert_eq!(part_two(&input), 6);
+        assert_eq!(part_two(&input), Ok(6));
+    }
+
+    #[test]
+    fn test_undefined_targets() {
+        let err = Input::from_str("LR\n\nAAA = (BBB, ZZZ)\nZZZ = (ZZZ, CCC)\n").unwrap_err();
+        assert_eq!(
+            err,
+            NetworkError::UndefinedTargets(vec![
+                ("AAA".into(), "BBB".into()),
+                ("ZZZ".into(), "CCC".into()),
+            ])
+        );
+        assert_eq!(
+            err.to_string(),
+            "nodes point at targets that aren't in the network: AAA -> BBB, ZZZ -> CCC"
+        );
+    }
+
+    #[test]
+    fn test_unsolvable_networks() {
+        // ZZZ exists but nothing points at it
+        let input = Input::from_str("L\n\nAAA = (AAA, AAA)\nZZZ = (ZZZ, ZZZ)\n").unwrap();
+        assert_eq!(
+            part_one(&input),
+            Err(Unsolvable::UnreachableGoal("AAA".into()))
+        );
+
+        // ZZZ is connected, but only ever going right, and the route only goes left
+        let input = Input::from_str("L\n\nAAA = (AAA, ZZZ)\nZZZ = (ZZZ, ZZZ)\n").unwrap();
+        assert_eq!(
+            part_one(&input),
+            Err(Unsolvable::NoExitInCycle("AAA".into()))
+        );
+
+        let input = Input::from_str("L\n\nBBB = (BBB, BBB)\n").unwrap();
+        assert_eq!(
+            part_one(&input),
+            Err(Unsolvable::MissingStart("AAA".into()))
+        );
+    }
+
+    #[test]
+    fn test_unsolvable_starts() {
+        let input = Input::from_str(
+            "L\n\n11A = (11Z, 11A)\n11Z = (11A, 11A)\n22A = (22A, 22Z)\n22Z = (22Z, 22Z)\n",
+        )
+        .unwrap();
+        assert_eq!(
+            input.unsolvable_starts(),
+            [Unsolvable::NoExitInCycle("22A".into())]
+        );
+        // the AAA -> ZZZ walk is checked while loading too
+        assert_eq!(
+            input.validation.part_one,
+            Some(Unsolvable::MissingStart("AAA".into()))
+        );
+
+        // the only exit is on the way into the cycle, so the starts can never line up on it
+        let input =
+            Input::from_str("L\n\n11A = (11Z, 11Z)\n11Z = (11B, 11B)\n11B = (11B, 11B)\n").unwrap();
+        assert!(input.check_start("11A", |node| node == "11Z").is_ok());
+        assert_eq!(
+            input.unsolvable_starts(),
+            [Unsolvable::NoExitInCycle("11A".into())]
+        );
+
+        let input = Input::from_str("L\n\n11A = (11A, 11A)\n11Z = (11Z, 11Z)\n").unwrap();
+        assert_eq!(
+            input.unsolvable_starts(),
+            [Unsolvable::UnreachableGoal("11A".into())]
+        );
+        assert!(part_two(&input).is_err());
+    }
+
+    #[test]
+    fn test_hand_written_network() {
+        // CRLF endings, comments, long names, and no trailing newline
+        let input_str = "# a hand written network\r\nLLR\r\n\r\nAAA = (BRANCH_1, BRANCH_1) # start\r\n# the long way round\r\nBRANCH_1 = (AAA, ZZZ)\r\nZZZ = (ZZZ, ZZZ)";
+        let input = Input::from_str(input_str).expect("Expected to parse");
+        assert_eq!(input.route, "LLR");
+        assert_eq!(input.mapping.len(), 3);
+        assert_eq!(input.mapping["BRANCH_1"].right, "ZZZ");
+        assert_eq!(part_one(&input), Ok(6));
     }
 }
@@ -1,33 +1,134 @@
 use std::collections::{HashSet, VecDeque};
-use std::{fs, str::FromStr};
+use std::path::Path;
+use std::{fs, io, str::FromStr};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
-enum PipeSegment {
+pub enum Direction {
+    North,
+    South,
+    West,
+    East,
+}
+
+impl Direction {
+    // same order we've always checked neighbors in: above, below, left, right
+    pub const ALL: [Direction; 4] = [
+        Direction::North,
+        Direction::South,
+        Direction::West,
+        Direction::East,
+    ];
+
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::North => Direction::South,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
+            Direction::East => Direction::West,
+        }
+    }
+}
+
+/// A set of directions packed into the low four bits of a byte
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Directions(u8);
+
+impl Directions {
+    pub fn contains(self, direction: Direction) -> bool {
+        self.0 & Directions::from(direction).0 != 0
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = Direction> {
+        Direction::ALL
+            .into_iter()
+            .filter(move |&direction| self.contains(direction))
+    }
+}
+
+impl From<Direction> for Directions {
+    fn from(direction: Direction) -> Self {
+        Directions(1 << direction as u8)
+    }
+}
+
+impl std::ops::BitOr<Direction> for Direction {
+    type Output = Directions;
+
+    fn bitor(self, rhs: Direction) -> Self::Output {
+        Directions::from(self) | rhs
+    }
+}
+
+impl std::ops::BitOr<Direction> for Directions {
+    type Output = Directions;
+
+    fn bitor(self, rhs: Direction) -> Self::Output {
+        Directions(self.0 | Directions::from(rhs).0)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PipeSegment {
     Vertical,   // a vertical bar
     Horizontal, // horizontal bar
     TopLeftCorner,
     TopRightCorner,
     BottomLeftCorner,
     BottomRightCorner,
-    PlusConnection, // can connect to any segment
+}
+
+impl PipeSegment {
+    const ALL: [PipeSegment; 6] = [
+        PipeSegment::Vertical,
+        PipeSegment::Horizontal,
+        PipeSegment::TopLeftCorner,
+        PipeSegment::TopRightCorner,
+        PipeSegment::BottomLeftCorner,
+        PipeSegment::BottomRightCorner,
+    ];
+
+    /// The directions this segment has an opening towards
+    pub fn openings(self) -> Directions {
+        use Direction::*;
+
+        match self {
+            PipeSegment::Vertical => North | South,
+            PipeSegment::Horizontal => West | East,
+            PipeSegment::TopLeftCorner => South | East,
+            PipeSegment::TopRightCorner => South | West,
+            PipeSegment::BottomLeftCorner => North | East,
+            PipeSegment::BottomRightCorner => North | West,
+        }
+    }
+
+    pub fn box_drawing(self) -> char {
+        match self {
+            PipeSegment::Vertical => '│',
+            PipeSegment::Horizontal => '─',
+            PipeSegment::TopLeftCorner => '┌',
+            PipeSegment::TopRightCorner => '┐',
+            PipeSegment::BottomLeftCorner => '└',
+            PipeSegment::BottomRightCorner => '┘',
+        }
+    }
+
+    pub fn symbol(self) -> char {
+        match self {
+            PipeSegment::Vertical => '|',
+            PipeSegment::Horizontal => '-',
+            PipeSegment::TopLeftCorner => 'F',
+            PipeSegment::TopRightCorner => '7',
+            PipeSegment::BottomLeftCorner => 'L',
+            PipeSegment::BottomRightCorner => 'J',
+        }
+    }
 }
 
 impl FromStr for PipeSegment {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        use PipeSegment::*;
-
-        match s.as_bytes()[0] as char {
-            '|' => Ok(Vertical),
-            '-' => Ok(Horizontal),
-            'L' => Ok(BottomLeftCorner),
-            'J' => Ok(BottomRightCorner),
-            '7' => Ok(TopRightCorner),
-            'F' => Ok(TopLeftCorner),
-            'S' => Ok(PlusConnection),
-            _ => Err(()),
-        }
+        s.chars().next().ok_or(())?.try_into()
     }
 }
 
@@ -35,17 +136,41 @@ impl TryFrom<char> for PipeSegment {
     type Error = ();
 
     fn try_from(value: char) -> Result<Self, Self::Error> {
-        use PipeSegment::*;
-
-        match value {
-            '|' => Ok(Vertical),
-            '-' => Ok(Horizontal),
-            'L' => Ok(BottomLeftCorner),
-            'J' => Ok(BottomRightCorner),
-            '7' => Ok(TopRightCorner),
-            'F' => Ok(TopLeftCorner),
-            'S' => Ok(PlusConnection),
-            _ => Err(()),
+        PipeSegment::ALL
+            .into_iter()
+            .find(|segment| segment.symbol() == value)
+            .ok_or(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MapError {
+    Empty,
+    // a line (counting from 1) that isn't as long as the first one
+    RaggedLine {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    StartNotOnLoop,
+    // the start could be any of these and still close a loop
+    AmbiguousStart(Vec<PipeSegment>),
+}
+
+impl std::fmt::Display for MapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapError::Empty => write!(f, "the map has no tiles"),
+            MapError::RaggedLine {
+                line,
+                expected,
+                found,
+            } => write!(f, "line {line} has {found} tiles, expected {expected}"),
+            MapError::StartNotOnLoop => write!(f, "no pipe under the start closes a loop"),
+            MapError::AmbiguousStart(shapes) => {
+                let symbols: String = shapes.iter().map(|shape| shape.symbol()).collect();
+                write!(f, "the start could be any of {symbols:?}")
+            }
         }
     }
 }
@@ -56,55 +181,48 @@ pub struct PipeMap {
     map: Vec<Option<PipeSegment>>,
     rows: usize,
     cols: usize,
+    // generated maps don't always mark a start
+    start: Option<(usize, usize)>,
 }
 
 impl std::fmt::Display for PipeMap {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for r in (0..self.rows) {
+        for r in 0..self.rows {
             let line: String = (0..self.cols)
-                .map(|c| {
-                    if let Some(pipe) = self.index(r, c) {
-                        match pipe {
-                            PipeSegment::Vertical => "|",
-                            PipeSegment::Horizontal => "-",
-                            PipeSegment::TopLeftCorner => "F",
-                            PipeSegment::TopRightCorner => "7",
-                            PipeSegment::BottomLeftCorner => "L",
-                            PipeSegment::BottomRightCorner => "J",
-                            PipeSegment::PlusConnection => "+",
-                        }
-                    } else {
-                        "."
-                    }
-                })
+                .map(|c| self.index(r, c).map_or('.', PipeSegment::symbol))
                 .collect();
-            write!(f, "{}\n", line)?;
+            writeln!(f, "{}", line)?;
         }
         Ok(())
     }
 }
 
 impl PipeMap {
-    pub fn find_start(&self) -> (usize, usize) {
-        // since we replaced our start with a plus connection, search for that
-        let idx = self
-            .map
-            .iter()
-            .enumerate()
-            .filter_map(|(i, element)| {
-                if let Some(segment) = element {
-                    if *segment == PipeSegment::PlusConnection {
-                        Some(i)
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
+    pub fn find_start(&self) -> Option<(usize, usize)> {
+        self.start
+    }
+
+    fn infer_start(&mut self) -> Result<(), MapError> {
+        // try every shape under the start, and keep the ones that connect to both
+        // neighbors and take us around a loop
+        let Some((row, col)) = self.start else {
+            return Ok(());
+        };
+        let shapes: Vec<_> = PipeSegment::ALL
+            .into_iter()
+            .filter(|&shape| {
+                self.map[row * self.cols + col] = Some(shape);
+                self.connections(row, col).len() == 2 && self.main_loop().is_some()
             })
-            .next()
-            .expect("Pipe Start");
-        (idx / self.cols, idx % self.cols)
+            .collect();
+        match shapes[..] {
+            [shape] => {
+                self.map[row * self.cols + col] = Some(shape);
+                Ok(())
+            }
+            [] => Err(MapError::StartNotOnLoop),
+            _ => Err(MapError::AmbiguousStart(shapes)),
+        }
     }
 
     pub fn index(&self, row: usize, col: usize) -> Option<PipeSegment> {
@@ -116,188 +234,502 @@ impl PipeMap {
         }
     }
 
-    pub fn set(&mut self, row: usize, col: usize, val: Option<PipeSegment>) {
-        self.map[row * self.cols + col] = val;
+    fn step(&self, row: usize, col: usize, direction: Direction) -> Option<(usize, usize)> {
+        // the neighboring tile in a direction, if it's still on the map
+        match direction {
+            Direction::North if row > 0 => Some((row - 1, col)),
+            Direction::South if row + 1 < self.rows => Some((row + 1, col)),
+            Direction::West if col > 0 => Some((row, col - 1)),
+            Direction::East if col + 1 < self.cols => Some((row, col + 1)),
+            _ => None,
+        }
     }
 
     pub fn connections(&self, row: usize, col: usize) -> Vec<(usize, usize)> {
-        use PipeSegment::*;
-        if let Some(segment) = self.index(row, col) {
-            // check for above, below, left, right
-            // doing module math to avoid an underflow
-            let above = if row == 0 {
-                None
-            } else {
-                match segment {
-                    Vertical | BottomLeftCorner | BottomRightCorner | PlusConnection => {
-                        let row_delta = (row + self.rows - 1) % self.rows;
-                        if let Some(neighbor) = self.index(row_delta, col) {
-                            match neighbor {
-                                Vertical | TopLeftCorner | TopRightCorner | PlusConnection => {
-                                    Some((row_delta, col))
-                                }
-                                _ => None,
-                            }
-                        } else {
-                            None
-                        }
-                    }
-                    _ => None,
+        // two tiles are connected if each one opens towards the other
+        let Some(segment) = self.index(row, col) else {
+            return vec![];
+        };
+        segment
+            .openings()
+            .iter()
+            .filter_map(|direction| {
+                let (r, c) = self.step(row, col, direction)?;
+                let neighbor = self.index(r, c)?;
+                neighbor
+                    .openings()
+                    .contains(direction.opposite())
+                    .then_some((r, c))
+            })
+            .collect()
+    }
+
+    pub fn traverse(&self) -> Option<(usize, Vec<(usize, usize)>)> {
+        // the farthest point is halfway around the loop, whichever way you go
+        self.main_loop().map(|path| (path.len() / 2, path))
+    }
+
+    pub fn main_loop(&self) -> Option<Vec<(usize, usize)>> {
+        self.walk_loop(self.find_start()?)
+    }
+
+    fn walk_loop(&self, start: (usize, usize)) -> Option<Vec<(usize, usize)>> {
+        // walk out of the start along each of its connections until one brings us back around,
+        // which gives the loop in the order it's traversed
+        self.connections(start.0, start.1)
+            .into_iter()
+            .find_map(|first| {
+                let mut path = vec![start];
+                let mut previous = start;
+                let mut current = first;
+                while current != start {
+                    path.push(current);
+                    let next = self
+                        .connections(current.0, current.1)
+                        .into_iter()
+                        .find(|&neighbor| neighbor != previous)?;
+                    previous = current;
+                    current = next;
                 }
-            };
-            let below = if row == self.rows - 1 {
-                None
-            } else {
-                match segment {
-                    Vertical | TopLeftCorner | TopRightCorner | PlusConnection => {
-                        let row_delta = row + 1;
-                        if let Some(neighbor) = self.index(row_delta, col) {
-                            match neighbor {
-                                Vertical | BottomLeftCorner | BottomRightCorner
-                                | PlusConnection => Some((row_delta, col)),
-                                _ => None,
-                            }
-                        } else {
-                            None
-                        }
+                Some(path)
+            })
+    }
+
+    pub fn interior(&self, path: &[(usize, usize)]) -> HashSet<(usize, usize)> {
+        // scan each row left to right, flipping between outside and inside every time we cross
+        // the loop. Picture the scan running just above the middle of each tile: it only cuts
+        // the pipe where the pipe opens north, so `|`, `L` and `J` flip us while `-`, `F` and `7`
+        // don't. A run like `L--7` crosses once and `L--J` twice, as it should. This relies on
+        // the start having already been swapped for its real segment
+        let pipe_nodes = HashSet::<(usize, usize)>::from_iter(path.iter().copied());
+        let mut interior = HashSet::new();
+        for r in 0..self.rows {
+            let mut inside = false;
+            for c in 0..self.cols {
+                if pipe_nodes.contains(&(r, c)) {
+                    if let Some(segment) = self.index(r, c) {
+                        inside ^= segment.openings().contains(Direction::North);
                     }
-                    _ => None,
+                } else if inside {
+                    interior.insert((r, c));
                 }
+            }
+        }
+        interior
+    }
+
+    fn upscale(&self, path: &[(usize, usize)]) -> Vec<Vec<bool>> {
+        // blow every loop tile up into a 3x3 glyph of walls, e.g. `F` becomes
+        //   ...
+        //   .##
+        //   .#.
+        // so there's always a gap to squeeze through between pipes that touch without connecting
+        let mut walls = vec![vec![false; self.cols * 3]; self.rows * 3];
+        for &(r, c) in path {
+            let Some(segment) = self.index(r, c) else {
+                continue;
             };
-            let left = if col == 0 {
-                None
-            } else {
-                match segment {
-                    Horizontal | TopRightCorner | BottomRightCorner | PlusConnection => {
-                        let col_delta = (col + self.cols - 1) % self.cols;
-                        if let Some(neighbor) = self.index(row, col_delta) {
-                            match neighbor {
-                                Horizontal | TopLeftCorner | BottomLeftCorner | PlusConnection => {
-                                    Some((row, col_delta))
-                                }
-                                _ => None,
-                            }
-                        } else {
-                            None
-                        }
-                    }
-                    _ => None,
+            let (y, x) = (r * 3 + 1, c * 3 + 1);
+            walls[y][x] = true;
+            for direction in segment.openings().iter() {
+                match direction {
+                    Direction::North => walls[y - 1][x] = true,
+                    Direction::South => walls[y + 1][x] = true,
+                    Direction::West => walls[y][x - 1] = true,
+                    Direction::East => walls[y][x + 1] = true,
                 }
-            };
-            let right = if col == self.cols - 1 {
-                None
+            }
+        }
+        walls
+    }
+
+    fn flood_from_border(walls: &[Vec<bool>]) -> Vec<Vec<bool>> {
+        let (height, width) = (walls.len(), walls.first().map_or(0, Vec::len));
+        let mut outside = vec![vec![false; width]; height];
+        let mut to_visit: VecDeque<(usize, usize)> = (0..height)
+            .flat_map(|y| [(y, 0), (y, width - 1)])
+            .chain((0..width).flat_map(|x| [(0, x), (height - 1, x)]))
+            .collect();
+        while let Some((y, x)) = to_visit.pop_front() {
+            if walls[y][x] || outside[y][x] {
+                continue;
+            }
+            outside[y][x] = true;
+            if y > 0 {
+                to_visit.push_back((y - 1, x));
+            }
+            if y + 1 < height {
+                to_visit.push_back((y + 1, x));
+            }
+            if x > 0 {
+                to_visit.push_back((y, x - 1));
+            }
+            if x + 1 < width {
+                to_visit.push_back((y, x + 1));
+            }
+        }
+        outside
+    }
+
+    pub fn interior_flood_fill(&self, path: &[(usize, usize)]) -> HashSet<(usize, usize)> {
+        // anything the flood couldn't reach from the edge of the upscaled map is inside the
+        // loop, and the center of each glyph tells us about the original tile
+        let pipe_nodes = HashSet::<(usize, usize)>::from_iter(path.iter().copied());
+        let outside = Self::flood_from_border(&self.upscale(path));
+        (0..self.rows)
+            .flat_map(|r| (0..self.cols).map(move |c| (r, c)))
+            .filter(|&(r, c)| !pipe_nodes.contains(&(r, c)) && !outside[r * 3 + 1][c * 3 + 1])
+            .collect()
+    }
+
+    /// The upscaled map used by [`PipeMap::interior_flood_fill`], with walls drawn as `#`,
+    /// flooded cells as `O`, and cells the flood never reached as `I`
+    pub fn render_flood_fill(&self) -> String {
+        let walls = self.upscale(&self.main_loop().unwrap_or_default());
+        let outside = Self::flood_from_border(&walls);
+        walls
+            .iter()
+            .zip(outside.iter())
+            .map(|(wall_row, outside_row)| {
+                let mut line: String = wall_row
+                    .iter()
+                    .zip(outside_row.iter())
+                    .map(|(&wall, &out)| match (wall, out) {
+                        (true, _) => '#',
+                        (_, true) => 'O',
+                        _ => 'I',
+                    })
+                    .collect();
+                line.push('\n');
+                line
+            })
+            .collect()
+    }
+
+    pub fn render(&self, options: RenderOptions) -> String {
+        let path = self.main_loop().unwrap_or_default();
+        let interior = self.interior(&path);
+        let pipe_nodes = HashSet::<(usize, usize)>::from_iter(path);
+        let paint = |text: char, style: &str| {
+            if options.color {
+                format!("{style}{text}{RESET}")
             } else {
-                match segment {
-                    Horizontal | TopLeftCorner | BottomLeftCorner | PlusConnection => {
-                        let col_delta = col + 1;
-                        if let Some(neighbor) = self.index(row, col_delta) {
-                            match neighbor {
-                                Horizontal | TopRightCorner | BottomRightCorner
-                                | PlusConnection => Some((row, col_delta)),
-                                _ => None,
-                            }
-                        } else {
-                            None
-                        }
+                text.to_string()
+            }
+        };
+
+        let mut rendered = String::new();
+        for r in 0..self.rows {
+            for c in 0..self.cols {
+                let on_loop = pipe_nodes.contains(&(r, c));
+                let tile = match self.index(r, c) {
+                    Some(segment) if Some((r, c)) == self.start => {
+                        paint(segment.box_drawing(), BOLD_RED)
                     }
-                    _ => None,
+                    Some(segment) if on_loop => paint(segment.box_drawing(), BOLD_YELLOW),
+                    Some(segment) if !options.dim_junk => segment.box_drawing().to_string(),
+                    Some(segment) if options.color => paint(segment.box_drawing(), DIM),
+                    // ground and hidden junk both fall through to being inside or outside
+                    _ if interior.contains(&(r, c)) => paint('I', GREEN),
+                    _ => paint('O', BLUE),
+                };
+                rendered.push_str(&tile);
+            }
+            rendered.push('\n');
+        }
+        rendered
+    }
+
+    pub fn to_ppm(&self, scale: usize) -> Vec<u8> {
+        // binary PPM, every tile becomes a scale x scale block with its pipe drawn as lines
+        // from the center of the block out to each opening
+        let path = self.main_loop().unwrap_or_default();
+        let interior = self.interior(&path);
+        let pipe_nodes = HashSet::<(usize, usize)>::from_iter(path);
+        let (width, height) = (self.cols * scale, self.rows * scale);
+        let mid = scale / 2;
+        let half_width = scale / 6;
+
+        let mut image = format!("P6\n{width} {height}\n255\n").into_bytes();
+        for y in 0..height {
+            for x in 0..width {
+                let (r, c) = (y / scale, x / scale);
+                let (py, px) = (y % scale, x % scale);
+                let on_pipe = self.index(r, c).is_some_and(|segment| {
+                    let openings = segment.openings();
+                    let in_column = px.abs_diff(mid) <= half_width;
+                    let in_row = py.abs_diff(mid) <= half_width;
+                    (in_column && in_row)
+                        || (in_column && py < mid && openings.contains(Direction::North))
+                        || (in_column && py > mid && openings.contains(Direction::South))
+                        || (in_row && px < mid && openings.contains(Direction::West))
+                        || (in_row && px > mid && openings.contains(Direction::East))
+                });
+                let color = if on_pipe && pipe_nodes.contains(&(r, c)) {
+                    LOOP_RGB
+                } else if interior.contains(&(r, c)) {
+                    INTERIOR_RGB
+                } else if on_pipe {
+                    JUNK_RGB
+                } else {
+                    EXTERIOR_RGB
+                };
+                image.extend_from_slice(&color);
+            }
+        }
+        image
+    }
+
+    pub fn to_svg(&self, scale: usize) -> String {
+        let path = self.main_loop().unwrap_or_default();
+        let interior = self.interior(&path);
+        let pipe_nodes = HashSet::<(usize, usize)>::from_iter(path.iter().copied());
+        let (width, height) = (self.cols * scale, self.rows * scale);
+        let center = |r: usize, c: usize| (c * scale + scale / 2, r * scale + scale / 2);
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\">\n"
+        );
+        svg += &format!(
+            "<rect width=\"{width}\" height=\"{height}\" fill=\"{}\"/>\n",
+            svg_color(EXTERIOR_RGB)
+        );
+        let mut interior: Vec<_> = interior.into_iter().collect();
+        interior.sort();
+        for (r, c) in interior {
+            svg += &format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{scale}\" height=\"{scale}\" fill=\"{}\"/>\n",
+                c * scale,
+                r * scale,
+                svg_color(INTERIOR_RGB)
+            );
+        }
+        // junk pipes are drawn tile by tile, from the center out to each opening
+        for r in 0..self.rows {
+            for c in 0..self.cols {
+                let Some(segment) = self.index(r, c) else {
+                    continue;
+                };
+                if pipe_nodes.contains(&(r, c)) {
+                    continue;
                 }
-            };
-            // println!("{above:?}, {below:?}, {left:?}, {right:?}");
-            [above, below, left, right]
-                .into_iter()
-                .filter_map(|neighbor| neighbor)
-                .collect()
-        } else {
-            vec![]
+                let (x, y) = center(r, c);
+                let half = scale / 2;
+                for direction in segment.openings().iter() {
+                    let (x2, y2) = match direction {
+                        Direction::North => (x, y - half),
+                        Direction::South => (x, y + half),
+                        Direction::West => (x - half, y),
+                        Direction::East => (x + half, y),
+                    };
+                    svg += &format!(
+                        "<line x1=\"{x}\" y1=\"{y}\" x2=\"{x2}\" y2=\"{y2}\" stroke=\"{}\"/>\n",
+                        svg_color(JUNK_RGB)
+                    );
+                }
+            }
         }
+        // the loop is already in order, so it's a single polygon through the tile centers
+        let points: Vec<String> = path
+            .iter()
+            .map(|&(r, c)| {
+                let (x, y) = center(r, c);
+                format!("{x},{y}")
+            })
+            .collect();
+        svg += &format!(
+            "<polygon points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\"/>\n",
+            points.join(" "),
+            svg_color(LOOP_RGB),
+            (scale / 3).max(1)
+        );
+        svg += "</svg>\n";
+        svg
+    }
+
+    /// Writes the map to `path` as a PPM or SVG image, picked from the file extension
+    pub fn export(&self, path: &Path, scale: usize) -> io::Result<()> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("ppm") => fs::write(path, self.to_ppm(scale)),
+            Some("svg") => fs::write(path, self.to_svg(scale)),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "expected a .ppm or .svg file",
+            )),
+        }
+    }
+
+    pub fn loop_area(&self) -> Option<f64> {
+        self.main_loop().map(|path| polygon_area(&path))
+    }
+
+    pub fn enclosed_tiles(&self) -> Option<usize> {
+        self.main_loop().map(|path| interior_points(&path))
     }
 
-    pub fn traverse(&mut self) -> (usize, Vec<(usize, usize)>) {
-        let start = self.find_start();
-        let mut to_visit = VecDeque::from([start]);
-        let mut visited = Vec::new();
-        let mut distance = 0;
-        // pull all the values off the deque and put any unvisited neighbors on
-        while to_visit.len() != 0 {
-            distance += 1;
-            for _ in 0..to_visit.len() {
-                // pop value off
-                let node = to_visit.pop_front().expect("value");
-                let neighbors = self.connections(node.0, node.1);
-                self.set(node.0, node.1, None); // mark as visited
-                visited.push(node);
-                // println!("{neighbors:?}");
-                for neighbor in neighbors.into_iter() {
-                    to_visit.push_back(neighbor)
+    pub fn all_loops(&self) -> Vec<PipeLoop> {
+        // group connected pipes together, and keep the groups where every pipe connects to
+        // exactly two others, those are the closed loops
+        let mut seen = HashSet::new();
+        let mut loops = Vec::new();
+        for r in 0..self.rows {
+            for c in 0..self.cols {
+                if self.index(r, c).is_none() || seen.contains(&(r, c)) {
+                    continue;
+                }
+                let mut component = Vec::new();
+                let mut to_visit = VecDeque::from([(r, c)]);
+                while let Some(node) = to_visit.pop_front() {
+                    if seen.insert(node) {
+                        component.push(node);
+                        to_visit.extend(self.connections(node.0, node.1));
+                    }
+                }
+                let closed = component
+                    .iter()
+                    .all(|&(r, c)| self.connections(r, c).len() == 2);
+                if !closed {
+                    continue;
+                }
+                if let Some(path) = self.walk_loop((r, c)) {
+                    loops.push(PipeLoop {
+                        length: path.len(),
+                        area: polygon_area(&path),
+                        enclosed_tiles: interior_points(&path),
+                        path,
+                    });
                 }
             }
         }
-        (distance - 1, visited)
+        loops
     }
 }
 
+/// A closed loop of pipe, ordered from its top-left most tile
+#[derive(Debug, Clone, PartialEq)]
+pub struct PipeLoop {
+    pub path: Vec<(usize, usize)>,
+    pub length: usize,
+    pub area: f64,
+    pub enclosed_tiles: usize,
+}
+
+fn doubled_area(path: &[(usize, usize)]) -> i64 {
+    // shoelace formula, left doubled so we stay in integers
+    path.iter()
+        .zip(path.iter().cycle().skip(1))
+        .map(|(&(r1, c1), &(r2, c2))| (c1 * r2) as i64 - (c2 * r1) as i64)
+        .sum::<i64>()
+        .abs()
+}
+
+/// Options for drawing a map with [`PipeMap::render`]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RenderOptions {
+    // use ANSI escapes to color the loop, start, and interior
+    pub color: bool,
+    // fade out pipes that aren't part of the loop, or hide them entirely without color
+    pub dim_junk: bool,
+}
+
+const RESET: &str = "\x1b[0m";
+const BOLD_YELLOW: &str = "\x1b[1;33m";
+const BOLD_RED: &str = "\x1b[1;31m";
+const DIM: &str = "\x1b[2m";
+const GREEN: &str = "\x1b[32m";
+const BLUE: &str = "\x1b[34m";
+
+// colors used when exporting images
+const EXTERIOR_RGB: [u8; 3] = [255, 255, 255];
+const INTERIOR_RGB: [u8; 3] = [120, 200, 120];
+const LOOP_RGB: [u8; 3] = [200, 40, 40];
+const JUNK_RGB: [u8; 3] = [190, 190, 190];
+
+fn svg_color([r, g, b]: [u8; 3]) -> String {
+    format!("rgb({r},{g},{b})")
+}
+
+/// Area of the polygon traced through the centers of the tiles in `path`
+pub fn polygon_area(path: &[(usize, usize)]) -> f64 {
+    doubled_area(path) as f64 / 2.0
+}
+
+/// Number of tiles strictly inside the loop traced by `path`
+pub fn interior_points(path: &[(usize, usize)]) -> usize {
+    // pick's theorem: A = i + b/2 - 1, every tile on the path is a boundary point
+    ((doubled_area(path) - path.len() as i64) / 2 + 1) as usize
+}
+
 impl FromStr for PipeMap {
-    type Err = ();
+    type Err = MapError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut lines = s.lines().peekable();
-        let cols = lines.peek().expect("line").len();
-        let mapping: Vec<Option<PipeSegment>> = lines
-            .flat_map(|line| line.chars())
+        PipeMap::parse(s, false)
+    }
+}
+
+impl PipeMap {
+    /// Parses a map, either rejecting lines that are a different length than the rest or
+    /// padding them out to the longest line with ground when `pad_ragged` is set
+    pub fn parse(s: &str, pad_ragged: bool) -> Result<Self, MapError> {
+        let lines: Vec<Vec<char>> = s
+            .trim_end_matches(['\r', '\n'])
+            .lines()
+            .map(|line| line.trim_end_matches('\r').chars().collect())
+            .collect();
+        let cols = if pad_ragged {
+            lines.iter().map(Vec::len).max()
+        } else {
+            lines.first().map(Vec::len)
+        }
+        .filter(|&cols| cols > 0)
+        .ok_or(MapError::Empty)?;
+        if let Some((i, line)) = lines
+            .iter()
+            .enumerate()
+            .find(|(_, line)| !pad_ragged && line.len() != cols)
+        {
+            return Err(MapError::RaggedLine {
+                line: i + 1,
+                expected: cols,
+                found: line.len(),
+            });
+        }
+
+        let rows = lines.len();
+        let chars: Vec<char> = lines
+            .into_iter()
+            .flat_map(|line| {
+                let padding = cols - line.len();
+                line.into_iter().chain(std::iter::repeat_n('.', padding))
+            })
+            .collect();
+        let start = chars.iter().position(|&letter| letter == 'S');
+        let mapping: Vec<Option<PipeSegment>> = chars
+            .into_iter()
             .map(|letter| letter.try_into().ok())
             .collect();
-        let rows = mapping.len() / cols;
-        Ok(PipeMap {
+        let mut map = PipeMap {
             map: mapping,
             rows,
             cols,
-        })
+            start: start.map(|start| (start / cols, start % cols)),
+        };
+        // swap the start out for whatever pipe has to be underneath it
+        map.infer_start()?;
+        Ok(map)
     }
 }
 
 fn part_one(input: &str) -> usize {
-    let mut map: PipeMap = input.parse().unwrap();
-    map.traverse().0
+    let map: PipeMap = input.parse().unwrap();
+    map.traverse().expect("a loop through the start").0
 }
 
 fn part_two(input: &str) -> u32 {
     let map: PipeMap = input.parse().unwrap();
-    let pipe_nodes = map.clone().traverse().1;
-    // make a new 2d vec of u8's to use in our sum
-    let mut mask: Vec<Vec<u32>> = vec![vec![0; map.cols]; map.rows];
-    for (r, c) in pipe_nodes.iter() {
-        // only count some nodes because of how we're doing our integral
-        // this was guess and check, but its so that you can distinguish what is and isn't
-        // in the polygon
-        mask[*r][*c] = if let Some(segment) = map.index(*r, *c) {
-            match segment {
-                PipeSegment::Vertical
-                | PipeSegment::BottomLeftCorner
-                | PipeSegment::BottomRightCorner => 1,
-                _ => 0,
-            }
-        } else {
-            0
-        }
-    }
-
-    let mut interior = 0;
-    // now iterate over every node in the map and do a sum
-    let pipe_nodes = HashSet::<(usize, usize)>::from_iter(pipe_nodes.into_iter());
-    for r in 0..map.rows {
-        for c in 0..map.cols {
-            if !pipe_nodes.contains(&(r, c)) {
-                let total: u32 = mask[r][0..c].iter().sum();
-                if total % 2 == 1 {
-                    // println!("Found interior cell at {r},{c}");
-                    interior += 1
-                }
-            }
-        }
-    }
-
-    interior
+    let pipe_nodes = map.traverse().expect("a loop through the start").1;
+    map.interior(&pipe_nodes).len() as u32
 }
 
 fn main() {
@@ -305,6 +737,37 @@ fn main() {
 
     println!("Part One Solution: {}", part_one(&input));
     println!("Part Two Solution: {}", part_two(&input));
+
+    if std::env::args().any(|arg| arg == "--cross-check") {
+        // flood filling is slower, but is an independent check on the parity trick
+        let map: PipeMap = input.parse().unwrap();
+        let pipe_nodes = map.traverse().expect("a loop through the start").1;
+        let parity = map.interior(&pipe_nodes);
+        let flood_fill = map.interior_flood_fill(&pipe_nodes);
+        println!(
+            "Parity: {} tiles, Flood Fill: {} tiles{}",
+            parity.len(),
+            flood_fill.len(),
+            if parity == flood_fill {
+                ""
+            } else {
+                " (disagree)"
+            }
+        );
+    }
+    if std::env::args().any(|arg| arg == "--render") {
+        let map: PipeMap = input.parse().unwrap();
+        let options = RenderOptions {
+            color: true,
+            dim_junk: true,
+        };
+        print!("{}", map.render(options));
+    }
+    if let Some(path) = std::env::args().skip_while(|arg| arg != "--export").nth(1) {
+        let map: PipeMap = input.parse().unwrap();
+        map.export(Path::new(&path), 9)
+            .expect("Expected to write image");
+    }
 }
 
 #[cfg(test)]
@@ -317,7 +780,82 @@ mod test {
         let map: PipeMap = input.parse().expect("Expect Parse to Work");
         assert_eq!(map.cols, 5);
         assert_eq!(map.rows, 5);
-        assert_eq!(map.find_start(), (2, 0));
+        assert_eq!(map.find_start(), Some((2, 0)));
+    }
+
+    #[test]
+    fn test_infer_start() {
+        let input = fs::read_to_string("test_input.txt").expect("Expected to load puzzle input");
+        let map: PipeMap = input.parse().expect("Expect Parse to Work");
+        assert_eq!(map.index(2, 0), Some(PipeSegment::TopLeftCorner));
+
+        let input = fs::read_to_string("test_input2.txt").expect("Expected to load puzzle input");
+        let map: PipeMap = input.parse().expect("Expect Parse to Work");
+        assert_eq!(map.index(0, 4), Some(PipeSegment::TopRightCorner));
+    }
+
+    #[test]
+    fn test_infer_start_errors() {
+        assert_eq!(
+            "...\n.S7\n.|.".parse::<PipeMap>().unwrap_err(),
+            MapError::StartNotOnLoop
+        );
+        // two loops cross at the start, so it could be either corner
+        let figure_eight = "F-7..\n|.|..\nL-S-7\n..|.|\n..L-J";
+        assert_eq!(
+            figure_eight.parse::<PipeMap>().unwrap_err(),
+            MapError::AmbiguousStart(vec![
+                PipeSegment::TopLeftCorner,
+                PipeSegment::BottomRightCorner
+            ])
+        );
+    }
+
+    #[test]
+    fn test_segment_openings() {
+        for segment in PipeSegment::ALL {
+            assert_eq!(segment.openings().iter().count(), 2);
+            assert_eq!(PipeSegment::try_from(segment.symbol()), Ok(segment));
+        }
+        let corner = PipeSegment::BottomRightCorner.openings();
+        assert!(corner.contains(Direction::North));
+        assert!(corner.contains(Direction::West));
+        assert!(!corner.contains(Direction::South));
+        assert!(!corner.contains(Direction::East));
+    }
+
+    #[test]
+    fn test_ragged_input() {
+        assert_eq!("".parse::<PipeMap>().unwrap_err(), MapError::Empty);
+        assert_eq!(
+            "S7.\nLJ\n...".parse::<PipeMap>().unwrap_err(),
+            MapError::RaggedLine {
+                line: 2,
+                expected: 3,
+                found: 2
+            }
+        );
+
+        assert_eq!(
+            "S7.\nLJ\n...".parse::<PipeMap>().unwrap_err().to_string(),
+            "line 2 has 2 tiles, expected 3"
+        );
+
+        // CRLF endings and trailing blank lines parse the same as the plain version
+        let input = fs::read_to_string("test_input.txt").expect("Expected to load puzzle input");
+        let crlf = format!("{}\r\n\r\n", input.lines().collect::<Vec<_>>().join("\r\n"));
+        let map: PipeMap = crlf.parse().expect("Expect Parse to Work");
+        assert_eq!((map.rows, map.cols), (5, 5));
+        assert_eq!(
+            map.to_string(),
+            input.parse::<PipeMap>().unwrap().to_string()
+        );
+
+        // padding fills short lines with ground
+        let map = PipeMap::parse("S7\nLJ.-\n.", true).expect("Expect Parse to Work");
+        assert_eq!((map.rows, map.cols), (3, 4));
+        assert_eq!(map.to_string(), "F7..\nLJ.-\n....\n");
+        assert_eq!(map.traverse().map(|(d, _)| d), Some(2));
     }
 
     #[test]
@@ -338,4 +876,187 @@ mod test {
         println!("{p2_soln}");
         assert_eq!(p2_soln, 10);
     }
+
+    #[test]
+    fn test_main_loop() {
+        let input = fs::read_to_string("test_input.txt").expect("Expected to load puzzle input");
+        let map: PipeMap = input.parse().expect("Expect Parse to Work");
+        let path = map.main_loop().expect("a loop through the start");
+        assert_eq!(path.len(), 16);
+        assert_eq!(path[0], (2, 0));
+        // every step is to an adjacent tile, including wrapping back to the start
+        for (a, b) in path.iter().zip(path.iter().cycle().skip(1)) {
+            assert_eq!(a.0.abs_diff(b.0) + a.1.abs_diff(b.1), 1);
+        }
+    }
+
+    #[test]
+    fn test_traverse() {
+        let input = fs::read_to_string("test_input.txt").expect("Expected to load puzzle input");
+        let map: PipeMap = input.parse().expect("Expect Parse to Work");
+        let (distance, path) = map.traverse().expect("a loop through the start");
+        assert_eq!(distance, 8);
+        assert_eq!(Some(path[0]), map.find_start());
+        assert_eq!(path[8], (2, 4));
+        // traversing leaves the map intact, so we can do it again
+        assert_eq!(map.traverse().map(|(d, _)| d), Some(8));
+        assert_eq!(part_one(&input), 8);
+    }
+
+    #[test]
+    fn test_render() {
+        let input = fs::read_to_string("test_input.txt").expect("Expected to load puzzle input");
+        let map: PipeMap = input.parse().expect("Expect Parse to Work");
+        let options = RenderOptions {
+            color: false,
+            dim_junk: true,
+        };
+        assert_eq!(map.render(options), "OO┌┐O\nO┌┘│O\n┌┘I└┐\n│┌──┘\n└┘OOO\n");
+        // without dimming, junk pipes are drawn like the rest
+        assert!(map.render(RenderOptions::default()).starts_with("┐─┌┐─\n"));
+        // colors only wrap the glyphs and dimmed junk is still drawn, so stripping them
+        // gives the plain undimmed rendering back
+        let colored = map.render(RenderOptions {
+            color: true,
+            dim_junk: true,
+        });
+        assert!(colored.contains(BOLD_RED));
+        let stripped = [RESET, BOLD_YELLOW, BOLD_RED, DIM, GREEN, BLUE]
+            .into_iter()
+            .fold(colored, |text, code| text.replace(code, ""));
+        assert_eq!(stripped, map.render(RenderOptions::default()));
+    }
+
+    #[test]
+    fn test_to_ppm() {
+        let input = fs::read_to_string("test_input.txt").expect("Expected to load puzzle input");
+        let map: PipeMap = input.parse().expect("Expect Parse to Work");
+        let scale = 6;
+        let image = map.to_ppm(scale);
+        let header = b"P6\n30 30\n255\n";
+        assert!(image.starts_with(header));
+        assert_eq!(image.len(), header.len() + 30 * 30 * 3);
+
+        let pixel = |y: usize, x: usize| {
+            let offset = header.len() + (y * 30 + x) * 3;
+            [image[offset], image[offset + 1], image[offset + 2]]
+        };
+        // center of the start tile is on the loop, the interior tile is filled, and the
+        // corner of a ground tile is left empty
+        assert_eq!(pixel(2 * scale + 3, 3), LOOP_RGB);
+        assert_eq!(pixel(2 * scale, 2 * scale), INTERIOR_RGB);
+        assert_eq!(pixel(scale, 0), EXTERIOR_RGB);
+        // junk pipes outside the loop are drawn, but faded
+        assert_eq!(pixel(3, 3), JUNK_RGB);
+    }
+
+    #[test]
+    fn test_export() {
+        let input = fs::read_to_string("test_input.txt").expect("Expected to load puzzle input");
+        let map: PipeMap = input.parse().expect("Expect Parse to Work");
+        let path = std::env::temp_dir().join("day_10_test_export.svg");
+        map.export(&path, 10).expect("Expected to write image");
+        let svg = fs::read_to_string(&path).expect("Expected to read image back");
+        fs::remove_file(&path).ok();
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("<polygon").count(), 1);
+        // one background rect plus one per interior tile
+        assert_eq!(svg.matches("<rect").count(), 2);
+        assert!(map.export(Path::new("map.bmp"), 10).is_err());
+    }
+
+    #[test]
+    fn test_flood_fill_squeeze() {
+        // the bottom two pockets are inside, the middle pocket can be reached by squeezing
+        // between the pipes
+        let input = "..........\n.S------7.\n.|F----7|.\n.||....||.\n.||....||.\n.|L-7F-J|.\n.|..||..|.\n.L--JL--J.\n..........";
+        let map: PipeMap = input.parse().expect("Expect Parse to Work");
+        let path = map.main_loop().expect("a loop through the start");
+        let interior = map.interior_flood_fill(&path);
+        assert_eq!(interior.len(), 4);
+        assert!(interior.contains(&(6, 2)));
+        assert!(!interior.contains(&(3, 3)));
+        assert_eq!(interior, map.interior(&path));
+        assert_eq!(part_two(input), 4);
+    }
+
+    #[test]
+    fn test_render_flood_fill() {
+        let input = fs::read_to_string("test_input.txt").expect("Expected to load puzzle input");
+        let map: PipeMap = input.parse().expect("Expect Parse to Work");
+        let view = map.render_flood_fill();
+        let lines: Vec<&str> = view.lines().collect();
+        assert_eq!(lines.len(), 15);
+        assert!(lines.iter().all(|line| line.len() == 15));
+        // the interior tile at (2, 2) is cut off from the flood, ground at (1, 0) isn't
+        assert_eq!(&lines[7][6..9], "III");
+        assert_eq!(&lines[4][0..3], "OOO");
+        // the loop through the start tile, an `F`
+        assert_eq!(&lines[6][0..3], "OOO");
+        assert_eq!(&lines[7][0..3], "O##");
+        assert_eq!(&lines[8][0..3], "O#I");
+    }
+
+    #[test]
+    fn test_all_loops() {
+        // the loop through the start, a decoy ring around one tile, and a dead end
+        let input = "S7.F-7\nLJ.|.|\n--.L-J";
+        let map: PipeMap = input.parse().expect("Expect Parse to Work");
+        let loops = map.all_loops();
+        assert_eq!(loops.len(), 2);
+        assert_eq!(Some(loops[0].path[0]), map.find_start());
+        assert_eq!(loops[0].length, 4);
+        assert_eq!(loops[0].enclosed_tiles, 0);
+        assert_eq!(loops[1].path[0], (0, 3));
+        assert_eq!(loops[1].length, 8);
+        assert_eq!(loops[1].area, 4.0);
+        assert_eq!(loops[1].enclosed_tiles, 1);
+
+        // generated maps without a start still parse, with a loop and a decoy inside it
+        let input = "F----7\n|F7..|\n|LJ.-|\nL----J";
+        let map: PipeMap = input.parse().expect("Expect Parse to Work");
+        assert_eq!(map.find_start(), None);
+        assert_eq!(map.main_loop(), None);
+        let loops = map.all_loops();
+        assert_eq!(loops.len(), 2);
+        assert_eq!((loops[0].length, loops[0].enclosed_tiles), (16, 8));
+        assert_eq!((loops[1].length, loops[1].enclosed_tiles), (4, 0));
+
+        // the real loop from the example is in there too, among the junk
+        let input = fs::read_to_string("test_input2.txt").expect("Expected to load puzzle input");
+        let map: PipeMap = input.parse().expect("Expect Parse to Work");
+        let main_loop = map.main_loop().unwrap();
+        assert!(
+            map.all_loops()
+                .iter()
+                .any(|pipe_loop| pipe_loop.length == main_loop.len()
+                    && pipe_loop.enclosed_tiles == 10)
+        );
+    }
+
+    #[test]
+    fn test_polygon_area() {
+        // a 3x3 ring of tiles around a single interior tile
+        let path = [
+            (0, 0),
+            (0, 1),
+            (0, 2),
+            (1, 2),
+            (2, 2),
+            (2, 1),
+            (2, 0),
+            (1, 0),
+        ];
+        assert_eq!(polygon_area(&path), 4.0);
+        assert_eq!(interior_points(&path), 1);
+    }
+
+    #[test]
+    fn test_enclosed_tiles_matches_scanline() {
+        for file in ["test_input.txt", "test_input2.txt"] {
+            let input = fs::read_to_string(file).expect("Expected to load puzzle input");
+            let map: PipeMap = input.parse().expect("Expect Parse to Work");
+            assert_eq!(map.enclosed_tiles(), Some(part_two(&input) as usize));
+        }
+    }
 }
@@ -1,26 +1,72 @@
-use itertools::Itertools;
-use std::collections::HashSet;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeSet, BinaryHeap, HashMap};
 use std::{fs, str::FromStr};
 
 struct SpaceMap {
+    rows: usize,
+    cols: usize,
     empty_rows: Vec<usize>,
     empty_cols: Vec<usize>,
     planets: Vec<(usize, usize)>,
 }
 
+#[derive(Debug, PartialEq, Eq)]
+enum SpaceMapError {
+    // every row of the image has to be as wide as the top one, `line` is 1-based like an editor
+    RaggedLine {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    InvalidGlyph {
+        row: usize,
+        col: usize,
+        glyph: char,
+    },
+}
+
+impl std::fmt::Display for SpaceMapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpaceMapError::RaggedLine {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {line} of the image is {found} cells wide, expected {expected}"
+            ),
+            SpaceMapError::InvalidGlyph { row, col, glyph } => {
+                write!(
+                    f,
+                    "{glyph:?} at row {row}, column {col} is neither '.' nor '#'"
+                )
+            }
+        }
+    }
+}
+
 impl FromStr for SpaceMap {
-    type Err = ();
+    type Err = SpaceMapError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut lines = s.lines().peekable();
-        let cols = lines.peek().unwrap().len();
-        let rows = s.len() / cols;
+        let lines: Vec<&str> = s.lines().map(|line| line.trim_end_matches('\r')).collect();
+        let rows = lines.len();
+        let cols = lines.first().map_or(0, |line| line.chars().count());
 
-        let mut empty_rows = HashSet::<usize>::from_iter(0..rows);
-        let mut empty_cols = HashSet::<usize>::from_iter(0..cols);
+        let mut empty_rows = BTreeSet::<usize>::from_iter(0..rows);
+        let mut empty_cols = BTreeSet::<usize>::from_iter(0..cols);
         let mut planets: Vec<(usize, usize)> = Vec::new();
 
-        for (r, line) in lines.enumerate() {
+        for (r, line) in lines.into_iter().enumerate() {
+            let found = line.chars().count();
+            if found != cols {
+                return Err(SpaceMapError::RaggedLine {
+                    line: r + 1,
+                    expected: cols,
+                    found,
+                });
+            }
             for (c, element) in line.chars().enumerate() {
                 match element {
                     '.' => {}
@@ -29,12 +75,20 @@ impl FromStr for SpaceMap {
                         empty_cols.remove(&c);
                         planets.push((r, c));
                     }
-                    _ => unreachable!(),
+                    glyph => {
+                        return Err(SpaceMapError::InvalidGlyph {
+                            row: r,
+                            col: c,
+                            glyph,
+                        })
+                    }
                 }
             }
         }
 
         Ok(Self {
+            rows,
+            cols,
             empty_rows: empty_rows.into_iter().collect(),
             empty_cols: empty_cols.into_iter().collect(),
             planets,
@@ -42,48 +96,429 @@ impl FromStr for SpaceMap {
     }
 }
 
+impl std::fmt::Display for SpaceMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let planets = BTreeSet::from_iter(self.planets.iter().copied());
+        for r in 0..self.rows {
+            let line: String = (0..self.cols)
+                .map(|c| if planets.contains(&(r, c)) { '#' } else { '.' })
+                .collect();
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// How far each empty line of space stretches once the universe expands
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Expansion {
+    row_factor: usize,
+    col_factor: usize,
+    // overrides the factor for individual empty rows or columns
+    row_weights: HashMap<usize, usize>,
+    col_weights: HashMap<usize, usize>,
+}
+
+impl Expansion {
+    fn uniform(factor: usize) -> Self {
+        Self::anisotropic(factor, factor)
+    }
+
+    fn anisotropic(row_factor: usize, col_factor: usize) -> Self {
+        Self {
+            row_factor,
+            col_factor,
+            row_weights: HashMap::new(),
+            col_weights: HashMap::new(),
+        }
+    }
+
+    fn row_width(&self, row: usize) -> usize {
+        *self.row_weights.get(&row).unwrap_or(&self.row_factor)
+    }
+
+    fn col_width(&self, col: usize) -> usize {
+        *self.col_weights.get(&col).unwrap_or(&self.col_factor)
+    }
+
+    fn validate(&self) -> Result<(), ExpansionError> {
+        // a line can grow as much as it likes, but it can't disappear
+        let widths = [self.row_factor, self.col_factor]
+            .into_iter()
+            .chain(self.row_weights.values().copied())
+            .chain(self.col_weights.values().copied());
+        for width in widths {
+            if width == 0 {
+                return Err(ExpansionError::ZeroFactor);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ExpansionError {
+    ZeroFactor,
+    // the expanded universe is too big to measure
+    Overflow,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum QueryError {
+    // galaxies are numbered from zero, so this is past the last one
+    UnknownGalaxy(usize),
+    Expansion(ExpansionError),
+}
+
+impl std::fmt::Display for ExpansionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExpansionError::ZeroFactor => {
+                write!(f, "expansion factors and weights have to be at least 1")
+            }
+            ExpansionError::Overflow => {
+                write!(f, "the expanded universe is too big to fit in a usize")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryError::UnknownGalaxy(galaxy) => {
+                write!(f, "there's no galaxy {galaxy}, they're numbered from 0")
+            }
+            QueryError::Expansion(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl From<ExpansionError> for QueryError {
+    fn from(err: ExpansionError) -> Self {
+        QueryError::Expansion(err)
+    }
+}
+
+impl FromStr for Expansion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // one setting per line, anything after a `#` is ignored
+        //   rows <factor>           every empty row
+        //   cols <factor>           every empty column
+        //   row <index> <weight>    a single empty row
+        //   col <index> <weight>    a single empty column
+        // lines that aren't configured don't expand at all
+        let mut expansion = Expansion::uniform(1);
+        for (i, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let numbers = words
+                .iter()
+                .skip(1)
+                .map(|word| word.parse::<usize>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| format!("Couldn't parse number on line {}", i + 1))?;
+            match (words.first(), &numbers[..]) {
+                (None, _) => {}
+                (Some(&"rows"), &[factor]) => expansion.row_factor = factor,
+                (Some(&"cols"), &[factor]) => expansion.col_factor = factor,
+                (Some(&"row"), &[row, weight]) => {
+                    expansion.row_weights.insert(row, weight);
+                }
+                (Some(&"col"), &[col, weight]) => {
+                    expansion.col_weights.insert(col, weight);
+                }
+                _ => return Err(format!("Couldn't parse line {}: {}", i + 1, line.trim())),
+            }
+        }
+        expansion
+            .validate()
+            .map_err(|_| "Expansion factors and weights must be at least 1".to_string())?;
+        Ok(expansion)
+    }
+}
+
+/// Ways of measuring the distance between two galaxies in expanded space
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Metric {
+    Manhattan,
+    Chebyshev,
+    Euclidean,
+}
+
+impl Metric {
+    const ALL: [Metric; 3] = [Metric::Manhattan, Metric::Chebyshev, Metric::Euclidean];
+
+    fn distance(self, (r1, c1): (usize, usize), (r2, c2): (usize, usize)) -> Distance {
+        // expanded coordinates can be well past what a f64 holds exactly, so only the
+        // euclidean distance leaves the integers
+        let (dr, dc) = (r1.abs_diff(r2) as u128, c1.abs_diff(c2) as u128);
+        match self {
+            Metric::Manhattan => Distance::Steps(dr + dc),
+            Metric::Chebyshev => Distance::Steps(dr.max(dc)),
+            Metric::Euclidean => Distance::Euclidean((dr as f64).hypot(dc as f64)),
+        }
+    }
+}
+
+/// A distance under some [`Metric`], exact for the ones that stay on the grid
+#[derive(Debug, Clone, Copy)]
+enum Distance {
+    Steps(u128),
+    Euclidean(f64),
+}
+
+impl Ord for Distance {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Distance::Steps(a), Distance::Steps(b)) => a.cmp(b),
+            (Distance::Euclidean(a), Distance::Euclidean(b)) => a.total_cmp(b),
+            // a query only ever uses one metric, but compare them as floats if they're mixed
+            (Distance::Steps(a), Distance::Euclidean(b)) => (*a as f64).total_cmp(b),
+            (Distance::Euclidean(a), Distance::Steps(b)) => a.total_cmp(&(*b as f64)),
+        }
+    }
+}
+
+impl PartialOrd for Distance {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Distance {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Distance {}
+
+impl std::fmt::Display for Distance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Distance::Steps(steps) => write!(f, "{steps}"),
+            Distance::Euclidean(distance) => write!(f, "{distance}"),
+        }
+    }
+}
+
 impl SpaceMap {
-    fn distance_between(
+    fn offsets(
+        len: usize,
+        empty: &[usize],
+        width: impl Fn(usize) -> usize,
+    ) -> Result<Vec<usize>, ExpansionError> {
+        // where each line starts once expanded, a running total of the widths of every line
+        // before it, with the total size of the expanded axis on the end. Lines with a galaxy
+        // in them never stretch
+        let mut offsets = Vec::with_capacity(len + 1);
+        let mut offset: usize = 0;
+        for line in 0..len {
+            offsets.push(offset);
+            let line_width = if empty.binary_search(&line).is_ok() {
+                width(line)
+            } else {
+                1
+            };
+            offset = offset
+                .checked_add(line_width)
+                .ok_or(ExpansionError::Overflow)?;
+        }
+        offsets.push(offset);
+        Ok(offsets)
+    }
+
+    fn expanded_offsets(
         &self,
-        (r1, c1): &(usize, usize),
-        (r2, c2): &(usize, usize),
-        expansion_coefficient: usize,
-    ) -> usize {
-        // do the manhattan distance plus addition for empty galaxies
-        let dr = if r1 < r2 { r2 - r1 } else { r1 - r2 };
-        let dc = if c1 < c2 { c2 - c1 } else { c1 - c2 };
-        let manhattan_dist = dr + dc;
-        let row_expansion = self
-            .empty_rows
-            .iter()
-            .filter(|&row| *row > *r1.min(r2) && *row < *r1.max(r2))
-            .count();
-        let column_expansion = self
-            .empty_cols
+        expansion: &Expansion,
+    ) -> Result<(Vec<usize>, Vec<usize>), ExpansionError> {
+        expansion.validate()?;
+        Ok((
+            Self::offsets(self.rows, &self.empty_rows, |row| expansion.row_width(row))?,
+            Self::offsets(self.cols, &self.empty_cols, |col| expansion.col_width(col))?,
+        ))
+    }
+
+    fn expanded_planets(
+        &self,
+        expansion: &Expansion,
+    ) -> Result<Vec<(usize, usize)>, ExpansionError> {
+        let (row_offsets, col_offsets) = self.expanded_offsets(expansion)?;
+        Ok(self
+            .planets
             .iter()
-            .filter(|&col| *col > *c1.min(c2) && *col < *c1.max(c2))
-            .count();
+            .map(|&(r, c)| (row_offsets[r], col_offsets[c]))
+            .collect())
+    }
 
-        manhattan_dist + (expansion_coefficient - 1) * (row_expansion + column_expansion)
+    fn distances_from(
+        &self,
+        galaxy: usize,
+        expansion: &Expansion,
+        metric: Metric,
+    ) -> Result<impl Iterator<Item = (usize, Distance)>, QueryError> {
+        // galaxies are numbered by their index into `planets`, i.e. reading order from zero
+        let planets = self.expanded_planets(expansion)?;
+        let origin = *planets
+            .get(galaxy)
+            .ok_or(QueryError::UnknownGalaxy(galaxy))?;
+        Ok(planets
+            .into_iter()
+            .enumerate()
+            .filter(move |&(other, _)| other != galaxy)
+            .map(move |(other, planet)| (other, metric.distance(origin, planet))))
     }
 
-    fn min_spanning_distances(&self, expansion_coefficient: usize) -> usize {
-        self.planets
-            .iter()
-            .combinations(2)
-            .map(|comb| self.distance_between(comb[0], comb[1], expansion_coefficient))
-            .sum()
+    fn nearest(
+        &self,
+        galaxy: usize,
+        expansion: &Expansion,
+        metric: Metric,
+    ) -> Result<Option<(usize, Distance)>, QueryError> {
+        // ties go to the galaxy that comes first
+        Ok(self
+            .distances_from(galaxy, expansion, metric)?
+            .min_by_key(|&(_, distance)| distance))
+    }
+
+    fn farthest(
+        &self,
+        galaxy: usize,
+        expansion: &Expansion,
+        metric: Metric,
+    ) -> Result<Option<(usize, Distance)>, QueryError> {
+        Ok(self
+            .distances_from(galaxy, expansion, metric)?
+            .min_by_key(|&(_, distance)| Reverse(distance)))
+    }
+
+    fn closest_pairs(
+        &self,
+        k: usize,
+        expansion: &Expansion,
+        metric: Metric,
+    ) -> Result<Vec<(usize, usize, Distance)>, ExpansionError> {
+        // keep the k best pairs seen so far in a max heap, so the worst of them is the one to
+        // drop. Ties are broken by the pair itself, which keeps them in reading order
+        let planets = self.expanded_planets(expansion)?;
+        let mut closest = BinaryHeap::with_capacity(k.min(planets.len().pow(2)) + 1);
+        for a in 0..planets.len() {
+            for b in (a + 1)..planets.len() {
+                closest.push((metric.distance(planets[a], planets[b]), a, b));
+                if closest.len() > k {
+                    closest.pop();
+                }
+            }
+        }
+        Ok(closest
+            .into_sorted_vec()
+            .into_iter()
+            .map(|(distance, a, b)| (a, b, distance))
+            .collect())
+    }
+
+    fn render_expanded(&self, expansion: &Expansion) -> Result<String, ExpansionError> {
+        // draws every line of the expanded universe, so only use this with small factors
+        let (row_offsets, col_offsets) = self.expanded_offsets(expansion)?;
+        let (rows, cols) = (row_offsets[self.rows], col_offsets[self.cols]);
+        let planets = BTreeSet::from_iter(self.expanded_planets(expansion)?);
+        Ok((0..rows)
+            .map(|r| {
+                let mut line: String = (0..cols)
+                    .map(|c| if planets.contains(&(r, c)) { '#' } else { '.' })
+                    .collect();
+                line.push('\n');
+                line
+            })
+            .collect())
+    }
+
+    fn render_compressed(&self, expansion: &Expansion) -> String {
+        // the map at its original size, with expanded rows drawn as `-`, expanded columns as
+        // `|`, and how many lines each expanded row stands for at the end of it
+        let planets = BTreeSet::from_iter(self.planets.iter().copied());
+        (0..self.rows)
+            .map(|r| {
+                let empty_row = self.empty_rows.binary_search(&r).is_ok();
+                let mut line: String = (0..self.cols)
+                    .map(|c| {
+                        let empty_col = self.empty_cols.binary_search(&c).is_ok();
+                        match (empty_row, empty_col) {
+                            _ if planets.contains(&(r, c)) => '#',
+                            (true, true) => '+',
+                            (true, false) => '-',
+                            (false, true) => '|',
+                            (false, false) => '.',
+                        }
+                    })
+                    .collect();
+                if empty_row {
+                    line += &format!(" x{}", expansion.row_width(r));
+                }
+                line.push('\n');
+                line
+            })
+            .collect()
+    }
+
+    fn render(&self, expansion: &Expansion) -> Result<String, ExpansionError> {
+        // fall back to the compressed view once the expanded one gets too big to read, or
+        // too big to even measure
+        const MAX_RENDERED_CELLS: usize = 200 * 200;
+        match self.expanded_offsets(expansion) {
+            Ok((rows, cols))
+                if rows[self.rows].saturating_mul(cols[self.cols]) <= MAX_RENDERED_CELLS =>
+            {
+                self.render_expanded(expansion)
+            }
+            Ok(_) | Err(ExpansionError::Overflow) => Ok(self.render_compressed(expansion)),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn min_spanning_distances(&self, expansion: &Expansion) -> Result<u128, ExpansionError> {
+        // manhattan distance splits into rows and columns, so sum each axis on its own
+        let (rows, cols): (Vec<_>, Vec<_>) = self.expanded_planets(expansion)?.into_iter().unzip();
+        pairwise_distance_sum(rows)?
+            .checked_add(pairwise_distance_sum(cols)?)
+            .ok_or(ExpansionError::Overflow)
+    }
+}
+
+fn pairwise_distance_sum(mut values: Vec<usize>) -> Result<u128, ExpansionError> {
+    // once sorted, each value is the far end of a pair with everything before it, so it
+    // contributes value * (number before it) - (sum of everything before it). Coordinates
+    // fit in a usize, but adding up every pair of them might not, so total in u128
+    values.sort_unstable();
+    let mut preceding: u128 = 0;
+    let mut total: u128 = 0;
+    for (i, value) in values.into_iter().enumerate() {
+        let value = value as u128;
+        let distance = value
+            .checked_mul(i as u128)
+            .ok_or(ExpansionError::Overflow)?
+            - preceding;
+        preceding += value;
+        total = total
+            .checked_add(distance)
+            .ok_or(ExpansionError::Overflow)?;
     }
+    Ok(total)
 }
 
-fn part_one(input: &str) -> usize {
+fn part_one(input: &str) -> u128 {
     let map: SpaceMap = input.parse().expect("parsing");
-    map.min_spanning_distances(2)
+    map.min_spanning_distances(&Expansion::uniform(2))
+        .expect("Expected the universe to fit")
 }
 
-fn part_two(input: &str) -> usize {
+fn part_two(input: &str) -> u128 {
     let map: SpaceMap = input.parse().expect("parsing");
-    map.min_spanning_distances(1000000)
+    map.min_spanning_distances(&Expansion::uniform(1000000))
+        .expect("Expected the universe to fit")
 }
 
 fn main() {
@@ -91,11 +526,96 @@ fn main() {
 
     println!("Part One Solution: {}", part_one(&input));
     println!("Part Two Solution: {}", part_two(&input));
+
+    if let Some(path) = std::env::args()
+        .skip_while(|arg| arg != "--expansion")
+        .nth(1)
+    {
+        let expansion: Expansion = fs::read_to_string(path)
+            .expect("Expected to load expansion weights")
+            .parse()
+            .expect("Expected to parse expansion weights");
+        let map: SpaceMap = input.parse().expect("parsing");
+        match map.min_spanning_distances(&expansion) {
+            Ok(soln) => println!("Custom Expansion Solution: {}", soln),
+            Err(err) => println!("Custom Expansion Failed: {}", err),
+        }
+    }
+    if let Some(factor) = std::env::args().skip_while(|arg| arg != "--render").nth(1) {
+        let factor = factor.parse().expect("Expected an expansion factor");
+        let map: SpaceMap = input.parse().expect("parsing");
+        match map.render(&Expansion::uniform(factor)) {
+            Ok(rendered) => print!("{}", rendered),
+            Err(err) => println!("Render Failed: {}", err),
+        }
+    }
+    if let Some(galaxy) = std::env::args().skip_while(|arg| arg != "--query").nth(1) {
+        let galaxy = galaxy.parse().expect("Expected a galaxy number");
+        let map: SpaceMap = input.parse().expect("parsing");
+        let expansion = Expansion::uniform(2);
+        for metric in Metric::ALL {
+            match (
+                map.nearest(galaxy, &expansion, metric),
+                map.farthest(galaxy, &expansion, metric),
+            ) {
+                (Ok(nearest), Ok(farthest)) => {
+                    println!("{metric:?}: nearest {nearest:?}, farthest {farthest:?}")
+                }
+                (Err(err), _) | (_, Err(err)) => println!("{metric:?}: query failed: {err}"),
+            }
+        }
+    }
+    if let Some(k) = std::env::args().skip_while(|arg| arg != "--closest").nth(1) {
+        let k = k.parse().expect("Expected a number of pairs");
+        let map: SpaceMap = input.parse().expect("parsing");
+        let pairs = map
+            .closest_pairs(k, &Expansion::uniform(2), Metric::Manhattan)
+            .unwrap();
+        for (a, b, distance) in pairs {
+            println!("{a} <-> {b}: {distance}");
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use itertools::Itertools;
+
+    impl SpaceMap {
+        // the original pair by pair approach, kept around to check the fast one against
+        fn distance_between(
+            &self,
+            (r1, c1): &(usize, usize),
+            (r2, c2): &(usize, usize),
+            expansion_coefficient: usize,
+        ) -> usize {
+            // do the manhattan distance plus addition for empty galaxies
+            let dr = r1.abs_diff(*r2);
+            let dc = c1.abs_diff(*c2);
+            let manhattan_dist = dr + dc;
+            let row_expansion = self
+                .empty_rows
+                .iter()
+                .filter(|&row| *row > *r1.min(r2) && *row < *r1.max(r2))
+                .count();
+            let column_expansion = self
+                .empty_cols
+                .iter()
+                .filter(|&col| *col > *c1.min(c2) && *col < *c1.max(c2))
+                .count();
+
+            manhattan_dist + (expansion_coefficient - 1) * (row_expansion + column_expansion)
+        }
+
+        fn reference_spanning_distances(&self, expansion_coefficient: usize) -> usize {
+            self.planets
+                .iter()
+                .combinations(2)
+                .map(|comb| self.distance_between(comb[0], comb[1], expansion_coefficient))
+                .sum()
+        }
+    }
 
     #[test]
     fn test_galaxy_distance() {
@@ -103,7 +623,245 @@ mod test {
         let map: SpaceMap = input.parse().expect("parsing");
         assert!(map.planets.contains(&(5, 1)));
         assert!(map.planets.contains(&(9, 4)));
-        assert_eq!(map.distance_between(&(5, 1), &(9, 4), 1), 9)
+        assert_eq!(map.distance_between(&(5, 1), &(9, 4), 2), 9)
+    }
+
+    #[test]
+    fn test_map_dimensions() {
+        let input = fs::read_to_string("test_input.txt").expect("Expected to load puzzle input");
+        let map: SpaceMap = input.parse().expect("parsing");
+        assert_eq!((map.rows, map.cols), (10, 10));
+        assert_eq!(map.empty_rows, vec![3, 7]);
+        assert_eq!(map.empty_cols, vec![2, 5, 8]);
+
+        // wide maps with CRLF endings
+        let map: SpaceMap = "#....\r\n.....\r\n...#.\r\n".parse().expect("parsing");
+        assert_eq!((map.rows, map.cols), (3, 5));
+        assert_eq!(map.empty_rows, vec![1]);
+        assert_eq!(map.empty_cols, vec![1, 2, 4]);
+        assert_eq!(map.to_string(), "#....\n.....\n...#.\n");
+    }
+
+    #[test]
+    fn test_map_errors() {
+        assert_eq!(
+            "#..\n.x.".parse::<SpaceMap>().err(),
+            Some(SpaceMapError::InvalidGlyph {
+                row: 1,
+                col: 1,
+                glyph: 'x'
+            })
+        );
+        assert_eq!(
+            "#..\n.#".parse::<SpaceMap>().err(),
+            Some(SpaceMapError::RaggedLine {
+                line: 2,
+                expected: 3,
+                found: 2
+            })
+        );
+        assert_eq!(
+            "#..\n.#".parse::<SpaceMap>().err().unwrap().to_string(),
+            "line 2 of the image is 2 cells wide, expected 3"
+        );
+        assert_eq!(
+            "#..\n.x.".parse::<SpaceMap>().err().unwrap().to_string(),
+            "'x' at row 1, column 1 is neither '.' nor '#'"
+        );
+    }
+
+    #[test]
+    fn test_expanded_planets() {
+        let input = fs::read_to_string("test_input.txt").expect("Expected to load puzzle input");
+        let map: SpaceMap = input.parse().expect("parsing");
+        let expanded = map.expanded_planets(&Expansion::uniform(2)).unwrap();
+        // galaxies 5 and 9 from the puzzle text
+        assert!(expanded.contains(&(6, 1)));
+        assert!(expanded.contains(&(11, 5)));
+        assert_eq!(
+            map.expanded_planets(&Expansion::uniform(1)),
+            Ok(map.planets.clone())
+        );
+    }
+
+    #[test]
+    fn test_render_expanded() {
+        let input = fs::read_to_string("test_input.txt").expect("Expected to load puzzle input");
+        let map: SpaceMap = input.parse().expect("parsing");
+        // straight from the puzzle text
+        let expected = "\
+....#........
+.........#...
+#............
+.............
+.............
+........#....
+.#...........
+............#
+.............
+.............
+.........#...
+#....#.......
+";
+        assert_eq!(
+            map.render_expanded(&Expansion::uniform(2)),
+            Ok(expected.into())
+        );
+        assert_eq!(map.render(&Expansion::uniform(2)), Ok(expected.into()));
+        assert_eq!(
+            map.render_expanded(&Expansion::uniform(1)),
+            Ok(input.to_string() + "\n")
+        );
+    }
+
+    #[test]
+    fn test_render_compressed() {
+        let input = fs::read_to_string("test_input.txt").expect("Expected to load puzzle input");
+        let map: SpaceMap = input.parse().expect("parsing");
+        let rendered = map.render(&Expansion::uniform(1000000)).unwrap();
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[0], "..|#.|..|.");
+        assert_eq!(lines[3], "--+--+--+- x1000000");
+    }
+
+    #[test]
+    fn test_pair_queries() {
+        let input = fs::read_to_string("test_input.txt").expect("Expected to load puzzle input");
+        let map: SpaceMap = input.parse().expect("parsing");
+        let expansion = Expansion::uniform(2);
+        // galaxies 5 and 9 from the puzzle text
+        assert_eq!(
+            map.distances_from(4, &expansion, Metric::Manhattan)
+                .unwrap()
+                .nth(7),
+            Some((8, Distance::Steps(9)))
+        );
+        // every pair is in there, so they add up to part one
+        let all_pairs = map
+            .closest_pairs(usize::MAX, &expansion, Metric::Manhattan)
+            .unwrap();
+        assert_eq!(all_pairs.len(), 36);
+        let total: u128 = all_pairs
+            .iter()
+            .map(|(_, _, distance)| match distance {
+                Distance::Steps(steps) => *steps,
+                Distance::Euclidean(_) => unreachable!(),
+            })
+            .sum();
+        assert_eq!(total, 374);
+        // the bounded search agrees with sorting every pair
+        let mut sorted = all_pairs.clone();
+        sorted.sort_by_key(|&(a, b, distance)| (distance, a, b));
+        assert_eq!(all_pairs, sorted);
+        assert_eq!(
+            map.closest_pairs(5, &expansion, Metric::Manhattan).unwrap(),
+            sorted[..5]
+        );
+        assert_eq!(
+            map.closest_pairs(0, &expansion, Metric::Manhattan),
+            Ok(vec![])
+        );
+
+        // three galaxies with a gap of empty space to either side of the first
+        let map: SpaceMap = "#...#\n.....\n#....".parse().expect("parsing");
+        assert_eq!(
+            map.nearest(0, &expansion, Metric::Manhattan),
+            Ok(Some((2, Distance::Steps(3))))
+        );
+        assert_eq!(
+            map.farthest(0, &expansion, Metric::Manhattan),
+            Ok(Some((1, Distance::Steps(7))))
+        );
+        // galaxies 0 and 2 are both 7 away, the first one wins
+        assert_eq!(
+            map.nearest(1, &expansion, Metric::Chebyshev),
+            Ok(Some((0, Distance::Steps(7))))
+        );
+        assert_eq!(
+            map.farthest(1, &expansion, Metric::Chebyshev),
+            Ok(Some((0, Distance::Steps(7))))
+        );
+        assert_eq!(
+            map.farthest(1, &expansion, Metric::Euclidean),
+            Ok(Some((2, Distance::Euclidean(58f64.sqrt()))))
+        );
+        assert_eq!(
+            map.closest_pairs(2, &expansion, Metric::Manhattan),
+            Ok(vec![(0, 2, Distance::Steps(3)), (0, 1, Distance::Steps(7))])
+        );
+
+        let lonely: SpaceMap = "#..".parse().expect("parsing");
+        assert_eq!(lonely.nearest(0, &expansion, Metric::Euclidean), Ok(None));
+        assert_eq!(
+            lonely.farthest(1, &expansion, Metric::Manhattan),
+            Err(QueryError::UnknownGalaxy(1))
+        );
+
+        // far enough apart that a f64 can't tell these distances apart
+        let map: SpaceMap = "#.#.#".parse().expect("parsing");
+        let expansion = Expansion::anisotropic(1, 1 << 60);
+        let nearest = map.nearest(0, &expansion, Metric::Manhattan).unwrap();
+        assert_eq!(nearest, Some((1, Distance::Steps((1 << 60) + 1))));
+        assert_eq!(
+            map.farthest(0, &expansion, Metric::Chebyshev),
+            Ok(Some((2, Distance::Steps((1 << 61) + 2))))
+        );
+    }
+
+    #[test]
+    fn test_matches_reference() {
+        for file in ["test_input.txt", "input.txt"] {
+            let input = fs::read_to_string(file).expect("Expected to load puzzle input");
+            let map: SpaceMap = input.parse().expect("parsing");
+            for expansion in [1, 2, 10, 100, 1000000] {
+                assert_eq!(
+                    map.min_spanning_distances(&Expansion::uniform(expansion)),
+                    Ok(map.reference_spanning_distances(expansion) as u128)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_pairwise_distance_sum() {
+        assert_eq!(pairwise_distance_sum(vec![]), Ok(0));
+        assert_eq!(pairwise_distance_sum(vec![5]), Ok(0));
+        // |1-4| + |1-3| + |4-3|
+        assert_eq!(pairwise_distance_sum(vec![1, 4, 3]), Ok(6));
+    }
+
+    #[test]
+    fn test_anisotropic_expansion() {
+        // one empty row and one empty column between two galaxies
+        let map: SpaceMap = "#..\n...\n..#".parse().expect("parsing");
+        assert_eq!(map.min_spanning_distances(&Expansion::uniform(2)), Ok(6));
+        assert_eq!(
+            map.min_spanning_distances(&Expansion::anisotropic(10, 1)),
+            Ok(13)
+        );
+
+        let mut weighted = Expansion::uniform(1);
+        weighted.row_weights.insert(1, 5);
+        // weights on lines with galaxies in them are ignored
+        weighted.col_weights.insert(0, 100);
+        assert_eq!(map.min_spanning_distances(&weighted), Ok(8));
+    }
+
+    #[test]
+    fn test_parse_expansion() {
+        let expansion: Expansion = "# comment\nrows 3\ncols 4 # trailing\n\nrow 7 10\ncol 2 1"
+            .parse()
+            .expect("parsing");
+        assert_eq!(expansion.row_factor, 3);
+        assert_eq!(expansion.col_factor, 4);
+        assert_eq!(expansion.row_width(7), 10);
+        assert_eq!(expansion.row_width(2), 3);
+        assert_eq!(expansion.col_width(2), 1);
+
+        assert!("rows".parse::<Expansion>().is_err());
+        assert!("row 1 x".parse::<Expansion>().is_err());
+        assert!("diagonal 2".parse::<Expansion>().is_err());
     }
 
     #[test]
@@ -117,7 +875,40 @@ mod test {
     fn test_part_two() {
         let input = fs::read_to_string("test_input.txt").expect("Expected to load puzzle input");
         let map: SpaceMap = input.parse().expect("parsing");
-        let soln = map.min_spanning_distances(10);
-        assert_eq!(soln, 1030)
+        let soln = map.min_spanning_distances(&Expansion::uniform(10));
+        assert_eq!(soln, Ok(1030))
+    }
+
+    #[test]
+    fn test_extreme_expansion() {
+        let input = fs::read_to_string("test_input.txt").expect("Expected to load puzzle input");
+        let map: SpaceMap = input.parse().expect("parsing");
+        assert_eq!(
+            map.min_spanning_distances(&Expansion::uniform(0)),
+            Err(ExpansionError::ZeroFactor)
+        );
+        assert!("rows 0".parse::<Expansion>().is_err());
+
+        // the total grows linearly with the factor, 374 at 2 and 1030 at 10, so we know what
+        // to expect even once it's far past what fits in a u64
+        let factor: usize = 1 << 62;
+        assert_eq!(
+            map.min_spanning_distances(&Expansion::uniform(factor)),
+            Ok(292 + 82 * (factor as u128 - 1))
+        );
+        assert_eq!(
+            map.min_spanning_distances(&Expansion::uniform(usize::MAX)),
+            Err(ExpansionError::Overflow)
+        );
+        assert_eq!(
+            ExpansionError::Overflow.to_string(),
+            "the expanded universe is too big to fit in a usize"
+        );
+        assert_eq!(
+            QueryError::UnknownGalaxy(7).to_string(),
+            "there's no galaxy 7, they're numbered from 0"
+        );
+        // too big to draw, but we can still show which lines expanded
+        assert!(map.render(&Expansion::uniform(usize::MAX)).is_ok());
     }
 }